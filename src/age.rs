@@ -0,0 +1,144 @@
+use crate::mounts;
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use std::fs;
+use std::path::Path;
+
+/// Which timestamp of a file an age rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeField {
+    Modified,
+    Accessed,
+    Changed,
+    Born,
+}
+
+impl TimeField {
+    pub fn from_name(name: &str) -> Result<TimeField, String> {
+        match name {
+            "mtime" | "modified" => Ok(TimeField::Modified),
+            "atime" | "accessed" => Ok(TimeField::Accessed),
+            "ctime" | "changed" => Ok(TimeField::Changed),
+            "btime" | "birth" | "created" => Ok(TimeField::Born),
+            _ => Err(format!("Unknown timestamp '{}', expected mtime, atime, ctime or btime", name)),
+        }
+    }
+
+    /// Reads this timestamp from `metadata`, or `None` when the platform or
+    /// the filesystem does not keep it.
+    pub fn of(&self, metadata: &fs::Metadata) -> Option<DateTime<Utc>> {
+        match self {
+            TimeField::Modified => metadata.modified().ok().map(DateTime::from),
+            TimeField::Accessed => metadata.accessed().ok().map(DateTime::from),
+            // On Linux the standard library reads the birth time with statx.
+            TimeField::Born => metadata.created().ok().map(DateTime::from),
+            TimeField::Changed => changed(metadata),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn changed(metadata: &fs::Metadata) -> Option<DateTime<Utc>> {
+    use std::os::unix::fs::MetadataExt;
+    DateTime::from_timestamp(metadata.ctime(), metadata.ctime_nsec() as u32)
+}
+
+#[cfg(not(unix))]
+fn changed(_metadata: &fs::Metadata) -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Parses a duration such as `90m`, `36h`, `2w` or `6mo`. A bare number is a
+/// number of days. Months count 30 days and years 365.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number.parse().map_err(|_| format!("Invalid duration '{}', expected e.g. 30d, 2w or 6mo", text))?;
+    let duration = match unit.trim() {
+        "s" => Duration::try_seconds(number),
+        "m" | "min" => Duration::try_minutes(number),
+        "h" => Duration::try_hours(number),
+        "" | "d" => Duration::try_days(number),
+        "w" => Duration::try_weeks(number),
+        "mo" => number.checked_mul(30).and_then(Duration::try_days),
+        "y" => number.checked_mul(365).and_then(Duration::try_days),
+        other => return Err(format!("Unknown duration unit '{}', expected s, m, h, d, w, mo or y", other)),
+    };
+    duration.ok_or_else(|| format!("Duration '{}' is too large", text))
+}
+
+/// Selects files whose timestamp falls between two ages, e.g. older than 30
+/// days but newer than a year.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgeRule {
+    pub field: TimeField,
+    pub older_than: Option<Duration>,
+    pub newer_than: Option<Duration>,
+}
+
+impl AgeRule {
+    /// Whether the file with `metadata` is in the age range at `now`. Files
+    /// without the chosen timestamp never match.
+    pub fn matches(&self, metadata: &fs::Metadata, now: DateTime<Utc>) -> bool {
+        let Some(time) = self.field.of(metadata) else { return false };
+        let age = now.signed_duration_since(time);
+        self.older_than.is_none_or(|min| age > min) && self.newer_than.is_none_or(|max| age < max)
+    }
+}
+
+/// Warns about scanned directories on filesystems mounted `noatime`, where
+/// access times are not kept up to date and atime rules cannot be trusted.
+pub fn warn_unreliable_atime<P: AsRef<Path>>(rule: &AgeRule, dirs_to_scan: &[P]) {
+    if rule.field != TimeField::Accessed {
+        return;
+    }
+    let mounts = mounts::mounts();
+    for dir in dirs_to_scan {
+        if let Some(mount) = mounts::mount_of(dir.as_ref(), &mounts) {
+            if mount.has_option("noatime") {
+                eprintln!("{}", format!(
+                    "Warning: {} is on {} mounted noatime; access times are not updated and atime rules are unreliable.",
+                    dir.as_ref().display(),
+                    mount.mount_point.display()
+                ).yellow());
+            }
+        }
+    }
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::days(14)));
+        assert_eq!(parse_duration("6mo"), Ok(Duration::days(180)));
+        assert_eq!(parse_duration("1y"), Ok(Duration::days(365)));
+        assert_eq!(parse_duration("30"), Ok(Duration::days(30)));
+        assert!(parse_duration("2 fortnights").is_err());
+        assert!(parse_duration("d").is_err());
+    }
+
+    #[test]
+    fn test_age_range_on_access_time() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.log");
+        fs::write(&path, "log").unwrap();
+        let two_months_ago = SystemTime::now() - std::time::Duration::from_secs(60 * 86400);
+        filetime::set_file_atime(&path, filetime::FileTime::from_system_time(two_months_ago)).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let now = Utc::now();
+
+        let range = AgeRule { field: TimeField::Accessed, older_than: Some(Duration::days(30)), newer_than: Some(Duration::days(365)) };
+        assert!(range.matches(&metadata, now));
+        assert!(!AgeRule { newer_than: Some(Duration::days(45)), ..range.clone() }.matches(&metadata, now));
+        // The file was just written, so its modification time is recent.
+        assert!(!AgeRule { field: TimeField::Modified, ..range }.matches(&metadata, now));
+    }
+}
@@ -0,0 +1,416 @@
+use crate::cleaner::deletion_failure;
+use crate::mounts;
+use crate::safe_delete;
+use crate::usage::human_size;
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    Firefox,
+    Chromium,
+}
+
+/// A browser with where each packaging keeps its profiles. Paths are
+/// relative to the configuration directory for Chromium browsers and to
+/// the home directory for Firefox.
+struct Browser {
+    name: &'static str,
+    family: Family,
+    dir: &'static str,
+    flatpak_id: &'static str,
+    snap_name: &'static str,
+}
+
+const BROWSERS: [Browser; 7] = [
+    Browser { name: "Firefox", family: Family::Firefox, dir: ".mozilla/firefox", flatpak_id: "org.mozilla.firefox", snap_name: "firefox" },
+    Browser { name: "Chrome", family: Family::Chromium, dir: "google-chrome", flatpak_id: "com.google.Chrome", snap_name: "" },
+    Browser { name: "Chromium", family: Family::Chromium, dir: "chromium", flatpak_id: "org.chromium.Chromium", snap_name: "chromium" },
+    Browser { name: "Brave", family: Family::Chromium, dir: "BraveSoftware/Brave-Browser", flatpak_id: "com.brave.Browser", snap_name: "brave" },
+    Browser { name: "Edge", family: Family::Chromium, dir: "microsoft-edge", flatpak_id: "com.microsoft.Edge", snap_name: "" },
+    Browser { name: "Vivaldi", family: Family::Chromium, dir: "vivaldi", flatpak_id: "com.vivaldi.Vivaldi", snap_name: "" },
+    Browser { name: "Opera", family: Family::Chromium, dir: "opera", flatpak_id: "com.opera.Opera", snap_name: "opera" },
+];
+
+/// Cache folders of a Firefox profile, found both in the profile and in its
+/// counterpart under the cache directory.
+const FIREFOX_CACHES: [&str; 4] = ["cache2", "startupCache", "thumbnails", "shader-cache"];
+
+/// Cache folders of a Chromium profile, found both in the profile and in its
+/// counterpart under the cache directory.
+const CHROMIUM_CACHES: [&str; 5] = ["Cache", "Code Cache", "GPUCache", "DawnWebGPUCache", "Service Worker/ScriptCache"];
+
+/// Where one packaging of a browser keeps its profiles and their caches.
+struct Installation {
+    browser: &'static Browser,
+    packaging: &'static str,
+    profiles_root: PathBuf,
+    cache_root: PathBuf,
+}
+
+/// A browser profile and the cache folders found for it.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub browser: &'static str,
+    pub packaging: &'static str,
+    pub name: String,
+    pub path: PathBuf,
+    pub cache_dirs: Vec<PathBuf>,
+    pub size: u64,
+    /// Whether the browser holds the profile lock, in which case its cache
+    /// is left alone.
+    pub in_use: bool,
+}
+
+fn installations(home: &Path, config_dir: &Path, cache_dir: &Path) -> Vec<Installation> {
+    let mut installations = Vec::new();
+    for browser in &BROWSERS {
+        let flatpak = home.join(".var").join("app").join(browser.flatpak_id);
+        let snap = home.join("snap").join(browser.snap_name);
+        let mut add = |packaging, profiles_root: PathBuf, cache_root: PathBuf| {
+            if profiles_root.is_dir() {
+                installations.push(Installation { browser, packaging, profiles_root, cache_root });
+            }
+        };
+        match browser.family {
+            Family::Firefox => {
+                let xdg = Path::new("mozilla").join("firefox");
+                add("Native", home.join(browser.dir), cache_dir.join(&xdg));
+                // Firefox follows the XDG layout since version 147.
+                add("Native", config_dir.join(&xdg), cache_dir.join(&xdg));
+                add("Flatpak", flatpak.join(browser.dir), flatpak.join("cache").join(&xdg));
+                add("Snap", snap.join("common").join(browser.dir), snap.join("common").join(".cache").join(&xdg));
+            }
+            Family::Chromium => {
+                add("Native", config_dir.join(browser.dir), cache_dir.join(browser.dir));
+                add("Flatpak", flatpak.join("config").join(browser.dir), flatpak.join("cache").join(browser.dir));
+                if !browser.snap_name.is_empty() {
+                    // The Chromium snap keeps its profiles outside of .config.
+                    add("Snap", snap.join("common").join(browser.snap_name), snap.join("common").join(".cache").join(browser.dir));
+                    add("Snap", snap.join("current").join(".config").join(browser.dir), snap.join("common").join(".cache").join(browser.dir));
+                }
+            }
+        }
+    }
+    installations
+}
+
+/// Reads the `[Profile*]` sections of a Firefox `profiles.ini` as display
+/// names and profile paths.
+fn parse_profiles_ini(text: &str, root: &Path) -> Vec<(String, PathBuf)> {
+    let mut profiles = Vec::new();
+    let mut section: Option<(Option<String>, Option<String>, bool)> = None;
+    let mut finish = |section: Option<(Option<String>, Option<String>, bool)>| {
+        if let Some((name, Some(path), is_relative)) = section {
+            let path = if is_relative { root.join(&path) } else { PathBuf::from(&path) };
+            profiles.push((name.unwrap_or(path.display().to_string()), path));
+        }
+    };
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            finish(section.take());
+            if line.starts_with("[Profile") {
+                section = Some((None, None, true));
+            }
+        } else if let (Some((name, path, is_relative)), Some((key, value))) = (section.as_mut(), line.split_once('=')) {
+            match key.trim() {
+                "Name" => *name = Some(value.trim().to_string()),
+                "Path" => *path = Some(value.trim().to_string()),
+                "IsRelative" => *is_relative = value.trim() == "1",
+                _ => {}
+            }
+        }
+    }
+    finish(section);
+    profiles
+}
+
+/// Reads the profile directories and display names listed under
+/// `profile.info_cache` in a Chromium `Local State` file.
+fn parse_local_state(text: &str) -> Vec<(String, String)> {
+    let Ok(state) = serde_json::from_str::<serde_json::Value>(text) else { return Vec::new() };
+    let Some(cache) = state.pointer("/profile/info_cache").and_then(|c| c.as_object()) else { return Vec::new() };
+    cache
+        .iter()
+        .map(|(dir, info)| {
+            let name = info.get("name").and_then(|n| n.as_str()).unwrap_or(dir);
+            (dir.clone(), name.to_string())
+        })
+        .collect()
+}
+
+/// Whether the lock at `lock` is held by a live process. Firefox and
+/// Chromium both make it a symlink naming the owner's host and ending in its
+/// PID, which tells a running browser from a stale lock left by a crash. The
+/// PID can only be checked for a native browser on this host: a lock taken
+/// on another host sharing the profile, or inside a Flatpak or Snap sandbox
+/// with its own PID namespace, is taken as held.
+fn is_locked(lock: &Path, sandboxed: bool) -> bool {
+    let Ok(target) = fs::read_link(lock) else { return lock.exists() };
+    let target = target.to_string_lossy();
+    let Some((host, pid)) = target.rsplit_once(['-', '+']) else { return true };
+    let host = host.trim_end_matches(':');
+    match pid.parse::<u32>() {
+        Ok(pid) if cfg!(target_os = "linux") && !sandboxed && is_this_host(host) => {
+            Path::new("/proc").join(pid.to_string()).exists()
+        }
+        _ => true,
+    }
+}
+
+/// Whether a lock's host part names this machine: Chromium writes the host
+/// name and Firefox an address it resolves to.
+fn is_this_host(host: &str) -> bool {
+    use std::net::{IpAddr, ToSocketAddrs};
+    let Some(name) = host_name() else { return false };
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback() || (name.as_str(), 0).to_socket_addrs().is_ok_and(|mut addrs| addrs.any(|a| a.ip() == ip)),
+        Err(_) => host == name,
+    }
+}
+
+#[cfg(unix)]
+fn host_name() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8(buffer[..end].to_vec()).ok()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> Option<String> {
+    None
+}
+
+fn directory_size(dir: &Path) -> u64 {
+    mounts::walk(WalkDir::new(dir))
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn profile(installation: &Installation, name: String, path: PathBuf, cache_path: PathBuf, in_use: bool) -> Profile {
+    let subfolders: &[&str] = match installation.browser.family {
+        Family::Firefox => &FIREFOX_CACHES,
+        Family::Chromium => &CHROMIUM_CACHES,
+    };
+    // Profiles outside the profiles root keep their cache inside.
+    let bases = if cache_path == path { vec![&path] } else { vec![&path, &cache_path] };
+    let cache_dirs: Vec<PathBuf> = bases
+        .into_iter()
+        .flat_map(|base| subfolders.iter().map(move |sub| base.join(sub)))
+        .filter(|dir| match fs::symlink_metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => {
+                // Recorded as found, so that a folder swapped for a symlink
+                // before cleaning is refused.
+                safe_delete::record(dir, &metadata);
+                true
+            }
+            _ => false,
+        })
+        .collect();
+    Profile {
+        browser: installation.browser.name,
+        packaging: installation.packaging,
+        name,
+        size: cache_dirs.iter().map(|dir| directory_size(dir)).sum(),
+        path,
+        cache_dirs,
+        in_use,
+    }
+}
+
+/// Finds the profiles of every supported browser for the user whose home,
+/// configuration and cache directories are given.
+pub fn discover_in(home: &Path, config_dir: &Path, cache_dir: &Path) -> Vec<Profile> {
+    let mut profiles = Vec::new();
+    for installation in installations(home, config_dir, cache_dir) {
+        let root = &installation.profiles_root;
+        match installation.browser.family {
+            Family::Firefox => {
+                let Ok(ini) = fs::read_to_string(root.join("profiles.ini")) else { continue };
+                for (name, path) in parse_profiles_ini(&ini, root) {
+                    // The cache of a profile mirrors its place under the profiles root.
+                    let cache_path = match path.strip_prefix(root) {
+                        Ok(relative) => installation.cache_root.join(relative),
+                        Err(_) => path.clone(),
+                    };
+                    let in_use = is_locked(&path.join("lock"), installation.packaging != "Native");
+                    profiles.push(profile(&installation, name, path, cache_path, in_use));
+                }
+            }
+            Family::Chromium => {
+                let Ok(state) = fs::read_to_string(root.join("Local State")) else { continue };
+                // One lock covers every profile of the installation.
+                let in_use = is_locked(&root.join("SingletonLock"), installation.packaging != "Native");
+                for (dir, name) in parse_local_state(&state) {
+                    let path = root.join(&dir);
+                    let cache_path = installation.cache_root.join(&dir);
+                    profiles.push(profile(&installation, name, path, cache_path, in_use));
+                }
+            }
+        }
+    }
+    profiles.sort_by(|a, b| a.browser.cmp(b.browser).then_with(|| a.path.cmp(&b.path)));
+    profiles
+}
+
+/// Finds the browser profiles of the current user.
+pub fn discover() -> Vec<Profile> {
+    let (Some(home), Some(config_dir), Some(cache_dir)) = (dirs::home_dir(), dirs::config_dir(), dirs::cache_dir()) else {
+        return Vec::new();
+    };
+    discover_in(&home, &config_dir, &cache_dir)
+}
+
+pub fn print_profiles_report(profiles: &[Profile]) {
+    let mut table = Table::new();
+    table.add_row(row!["Browser".bold().blue(), "Profile".bold().blue(), "Path".bold().blue(), "Cache Folders".bold().blue(), "Size".bold().blue(), "Status".bold().blue()]);
+    for profile in profiles {
+        let status = if profile.in_use { "Running".yellow() } else { "Ready".green() };
+        table.add_row(Row::new(vec![
+            Cell::new(&format!("{} ({})", profile.browser, profile.packaging)),
+            Cell::new(&profile.name),
+            Cell::new(&profile.path.display().to_string().blue().to_string()),
+            Cell::new(&profile.cache_dirs.len().to_string()),
+            Cell::new(&human_size(profile.size)),
+            Cell::new(&status.to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Deletes the cache folders of `profiles`, which the browsers recreate on
+/// their next start, and returns the space freed. Profiles of a running
+/// browser are skipped.
+pub fn clean_profiles(profiles: &[Profile]) -> u64 {
+    let mut table = Table::new();
+    table.add_row(row!["Cache Folder".bold().blue(), "Status".bold().blue()]);
+    let mut freed = 0;
+
+    for profile in profiles {
+        if profile.in_use {
+            eprintln!("{}", format!("Skipping {} profile '{}': the browser is running", profile.browser, profile.name).yellow());
+            continue;
+        }
+        for dir in &profile.cache_dirs {
+            let size = directory_size(dir);
+            let status = match safe_delete::remove_dir_all(dir) {
+                Ok(_) => {
+                    freed += size;
+                    "Deleted".to_string()
+                }
+                Err(e) => deletion_failure(dir, &e),
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&dir.display().to_string().blue().to_string()),
+                Cell::new(&status.green().to_string()),
+            ]));
+        }
+    }
+
+    table.printstd();
+    println!("{}", format!("Space freed: {}", human_size(freed)).green());
+    freed
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_discover_and_clean_profiles() {
+        let home = tempdir().unwrap();
+        let (config, cache) = (home.path().join(".config"), home.path().join(".cache"));
+
+        let firefox = home.path().join(".mozilla").join("firefox");
+        fs::create_dir_all(firefox.join("abc.default-release").join("cache2")).unwrap();
+        fs::create_dir_all(cache.join("mozilla").join("firefox").join("abc.default-release").join("cache2")).unwrap();
+        fs::write(cache.join("mozilla").join("firefox").join("abc.default-release").join("cache2").join("entry"), vec![0u8; 100]).unwrap();
+        fs::write(firefox.join("abc.default-release").join("places.sqlite"), "history").unwrap();
+        fs::write(firefox.join("profiles.ini"), "[Install4F96D1932A9F858E]\nDefault=abc.default-release\n\n[Profile0]\nName=default-release\nIsRelative=1\nPath=abc.default-release\n").unwrap();
+
+        let brave = home.path().join(".var").join("app").join("com.brave.Browser").join("config").join("BraveSoftware").join("Brave-Browser");
+        fs::create_dir_all(brave.join("Profile 1").join("GPUCache")).unwrap();
+        fs::write(brave.join("Profile 1").join("GPUCache").join("data_0"), vec![0u8; 50]).unwrap();
+        fs::write(brave.join("Profile 1").join("Bookmarks"), "{}").unwrap();
+        fs::write(brave.join("Local State"), r#"{"profile":{"info_cache":{"Profile 1":{"name":"Work"}}}}"#).unwrap();
+
+        let profiles = discover_in(home.path(), &config, &cache);
+        assert_eq!(profiles.len(), 2);
+        assert_eq!((profiles[0].browser, profiles[0].packaging, profiles[0].name.as_str()), ("Brave", "Flatpak", "Work"));
+        assert_eq!((profiles[1].browser, profiles[1].name.as_str()), ("Firefox", "default-release"));
+        assert_eq!(profiles[1].cache_dirs.len(), 2);
+        assert_eq!(profiles[1].size, 100);
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("127.0.1.1:+4294967", firefox.join("abc.default-release").join("lock")).unwrap();
+        let profiles = discover_in(home.path(), &config, &cache);
+        // A lock whose process is gone is stale.
+        assert!(profiles.iter().all(|p| !p.in_use));
+
+        assert_eq!(clean_profiles(&profiles), 150);
+        assert!(!brave.join("Profile 1").join("GPUCache").exists());
+        assert!(brave.join("Profile 1").join("Bookmarks").exists());
+        assert!(firefox.join("abc.default-release").join("places.sqlite").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lock_from_another_host_or_sandbox_is_held() {
+        let dir = tempdir().unwrap();
+        let lock = dir.path().join("SingletonLock");
+        let host = host_name().unwrap();
+        // No process has this PID, so only a lock checked on this host is stale.
+        std::os::unix::fs::symlink(format!("{}-4294967", host), &lock).unwrap();
+        assert!(!is_locked(&lock, false));
+        assert!(is_locked(&lock, true));
+
+        fs::remove_file(&lock).unwrap();
+        std::os::unix::fs::symlink(format!("{}-elsewhere-4294967", host), &lock).unwrap();
+        assert!(is_locked(&lock, false));
+    }
+
+    #[test]
+    fn test_running_browser_is_skipped() {
+        let home = tempdir().unwrap();
+        let chrome = home.path().join(".config").join("google-chrome");
+        fs::create_dir_all(chrome.join("Default").join("Cache")).unwrap();
+        fs::write(chrome.join("Local State"), r#"{"profile":{"info_cache":{"Default":{"name":"Person 1"}}}}"#).unwrap();
+        fs::write(chrome.join("SingletonLock"), "").unwrap();
+
+        let profiles = discover_in(home.path(), &home.path().join(".config"), &home.path().join(".cache"));
+        assert_eq!(profiles.len(), 1);
+        assert!(profiles[0].in_use);
+        assert_eq!(clean_profiles(&profiles), 0);
+        assert!(chrome.join("Default").join("Cache").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_swapped_for_symlink_after_discovery_is_kept() {
+        let home = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let chrome = home.path().join(".config").join("google-chrome");
+        fs::create_dir_all(chrome.join("Default").join("Cache")).unwrap();
+        fs::write(chrome.join("Local State"), r#"{"profile":{"info_cache":{"Default":{"name":"Person 1"}}}}"#).unwrap();
+        fs::write(outside.path().join("keep.txt"), "keep me").unwrap();
+
+        let profiles = discover_in(home.path(), &home.path().join(".config"), &home.path().join(".cache"));
+        assert_eq!(profiles[0].cache_dirs.len(), 1);
+        fs::remove_dir(chrome.join("Default").join("Cache")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), chrome.join("Default").join("Cache")).unwrap();
+
+        assert_eq!(clean_profiles(&profiles), 0);
+        assert_eq!(fs::read_to_string(outside.path().join("keep.txt")).unwrap(), "keep me");
+    }
+}
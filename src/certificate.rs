@@ -0,0 +1,274 @@
+use chrono::{DateTime, Utc};
+use colored::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const STANDARD: &str = "NIST SP 800-88 Rev. 1";
+pub const METHOD: &str = "Clear";
+pub const TECHNIQUE: &str = "Overwrite with pseudo-random data, flush to disk, then unlink";
+pub const VERIFICATION: &str = "Read-back of the final overwrite pass compared against the written pattern";
+
+/// Outcome of sanitizing a single file, as recorded on the certificate.
+#[derive(Debug, Clone, Serialize)]
+pub struct SanitizedFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256_before: Option<String>,
+    pub passes: u32,
+    pub verified: bool,
+    pub deleted: bool,
+    pub error: Option<String>,
+}
+
+impl SanitizedFile {
+    pub fn failed(path: &Path, size: u64, sha256_before: Option<String>, passes: u32, error: &io::Error) -> Self {
+        SanitizedFile {
+            path: path.display().to_string(),
+            size,
+            sha256_before,
+            passes,
+            verified: false,
+            deleted: false,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Integrity digest of a certificate. It is not a signature: no key is
+/// involved, so it shows accidental changes but anyone editing the
+/// certificate can compute a new one.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityDigest {
+    pub computed_at: DateTime<Utc>,
+    /// SHA-256 of the certificate serialized without this digest block.
+    pub sha256: String,
+}
+
+/// Record of a secure-clean run modeled on the NIST 800-88 sample certificate.
+#[derive(Debug, Clone, Serialize)]
+pub struct SanitizationCertificate {
+    pub certificate_id: String,
+    pub standard: String,
+    pub method: String,
+    pub technique: String,
+    pub passes: u32,
+    pub verification_method: String,
+    pub host: String,
+    pub user: String,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub verified_files: usize,
+    pub failed_files: usize,
+    pub result: String,
+    pub files: Vec<SanitizedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<IntegrityDigest>,
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn current_host() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub fn certificates_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rustcleaner")
+        .join("certificates")
+}
+
+impl SanitizationCertificate {
+    pub fn new(files: Vec<SanitizedFile>, passes: u32, started_at: DateTime<Utc>) -> Self {
+        use rand::Rng;
+        let completed_at = Utc::now();
+        let total_bytes = files.iter().map(|f| f.size).sum();
+        let verified_files = files.iter().filter(|f| f.verified && f.deleted).count();
+        let failed_files = files.len() - verified_files;
+        let result = if failed_files == 0 {
+            "Sanitization completed and verified"
+        } else {
+            "Sanitization completed with failures"
+        };
+
+        let mut certificate = SanitizationCertificate {
+            certificate_id: format!("RC-{}-{:08x}", started_at.format("%Y%m%dT%H%M%SZ"), rand::thread_rng().gen::<u32>()),
+            standard: STANDARD.to_string(),
+            method: METHOD.to_string(),
+            technique: TECHNIQUE.to_string(),
+            passes,
+            verification_method: VERIFICATION.to_string(),
+            host: current_host(),
+            user: current_user(),
+            started_at,
+            completed_at,
+            total_files: files.len(),
+            total_bytes,
+            verified_files,
+            failed_files,
+            result: result.to_string(),
+            files,
+            digest: None,
+        };
+        certificate.seal();
+        certificate
+    }
+
+    fn body_digest(&self) -> String {
+        let mut body = self.clone();
+        body.digest = None;
+        let body = serde_json::to_vec(&body).expect("certificate is serializable");
+        format!("{:x}", Sha256::digest(&body))
+    }
+
+    fn seal(&mut self) {
+        self.digest = Some(IntegrityDigest {
+            computed_at: Utc::now(),
+            sha256: self.body_digest(),
+        });
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("certificate is serializable")
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let rule = "=".repeat(72);
+        let _ = writeln!(out, "{}", rule);
+        let _ = writeln!(out, "CERTIFICATE OF SANITIZATION");
+        let _ = writeln!(out, "{}", rule);
+        let _ = writeln!(out, "Certificate ID:       {}", self.certificate_id);
+        let _ = writeln!(out, "Standard:             {}", self.standard);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "PERSON PERFORMING SANITIZATION");
+        let _ = writeln!(out, "  User:               {}", self.user);
+        let _ = writeln!(out, "  Host:               {}", self.host);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "SANITIZATION DETAILS");
+        let _ = writeln!(out, "  Method:             {}", self.method);
+        let _ = writeln!(out, "  Technique:          {}", self.technique);
+        let _ = writeln!(out, "  Passes:             {}", self.passes);
+        let _ = writeln!(out, "  Verification:       {}", self.verification_method);
+        let _ = writeln!(out, "  Started:            {}", self.started_at.to_rfc3339());
+        let _ = writeln!(out, "  Completed:          {}", self.completed_at.to_rfc3339());
+        let _ = writeln!(out);
+        let _ = writeln!(out, "MEDIA");
+        let _ = writeln!(out, "  Files:              {}", self.total_files);
+        let _ = writeln!(out, "  Total size:         {} bytes", self.total_bytes);
+        let _ = writeln!(out, "  Verified:           {}", self.verified_files);
+        let _ = writeln!(out, "  Failed:             {}", self.failed_files);
+        let _ = writeln!(out, "  Result:             {}", self.result);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "FILES");
+        for file in &self.files {
+            let status = match (&file.error, file.verified) {
+                (Some(e), _) => format!("FAILED ({})", e),
+                (None, true) => "VERIFIED".to_string(),
+                (None, false) => "NOT VERIFIED".to_string(),
+            };
+            let _ = writeln!(out, "  {}", file.path);
+            let _ = writeln!(out, "    size: {} bytes, passes: {}, status: {}", file.size, file.passes, status);
+            let _ = writeln!(out, "    sha256 before wipe: {}", file.sha256_before.as_deref().unwrap_or("n/a"));
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "INTEGRITY DIGEST (not a signature)");
+        if let Some(digest) = &self.digest {
+            let _ = writeln!(out, "  Computed at:        {}", digest.computed_at.to_rfc3339());
+            let _ = writeln!(out, "  SHA-256:            {}", digest.sha256);
+        }
+        let _ = writeln!(out, "{}", rule);
+        out
+    }
+
+    /// Writes the JSON and text forms next to each other and returns their paths.
+    pub fn save_to(&self, dir: &Path) -> io::Result<(PathBuf, PathBuf)> {
+        fs::create_dir_all(dir)?;
+        let json_path = dir.join(format!("{}.json", self.certificate_id));
+        let text_path = dir.join(format!("{}.txt", self.certificate_id));
+        fs::write(&json_path, self.to_json())?;
+        fs::write(&text_path, self.to_text())?;
+        Ok((json_path, text_path))
+    }
+
+    pub fn save(&self) -> io::Result<(PathBuf, PathBuf)> {
+        self.save_to(&certificates_dir())
+    }
+}
+
+/// Builds, seals and stores the certificate for a run, printing where it went.
+pub fn issue(files: Vec<SanitizedFile>, passes: u32, started_at: DateTime<Utc>) -> SanitizationCertificate {
+    let certificate = SanitizationCertificate::new(files, passes, started_at);
+    match certificate.save() {
+        Ok((json_path, text_path)) => {
+            println!("{}", format!("Sanitization certificate {} written to:", certificate.certificate_id).green());
+            println!("{}", format!("  {}", json_path.display()).green());
+            println!("{}", format!("  {}", text_path.display()).green());
+        }
+        Err(e) => eprintln!("{}", format!("Failed to write sanitization certificate: {}", e).red()),
+    }
+    certificate
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample_file(path: &str, verified: bool) -> SanitizedFile {
+        SanitizedFile {
+            path: path.to_string(),
+            size: 17,
+            sha256_before: Some("abc".to_string()),
+            passes: 1,
+            verified,
+            deleted: verified,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_certificate_digest_detects_changes() {
+        let mut certificate = SanitizationCertificate::new(vec![sample_file("/tmp/a.log", true)], 1, Utc::now());
+        let digest = certificate.digest.clone().unwrap().sha256;
+        assert_eq!(digest, certificate.body_digest());
+        assert_eq!(certificate.failed_files, 0);
+
+        certificate.files[0].path = "/tmp/other.log".to_string();
+        assert_ne!(digest, certificate.body_digest());
+    }
+
+    #[test]
+    fn test_certificate_save_writes_json_and_text() {
+        let dir = tempdir().unwrap();
+        let certificate = SanitizationCertificate::new(
+            vec![sample_file("/tmp/a.log", true), sample_file("/tmp/b.log", false)],
+            1,
+            Utc::now(),
+        );
+        let (json_path, text_path) = certificate.save_to(dir.path()).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(json_path).unwrap()).unwrap();
+        assert_eq!(json["total_files"], 2);
+        assert_eq!(json["failed_files"], 1);
+        assert_eq!(json["files"][0]["sha256_before"], "abc");
+
+        let text = fs::read_to_string(text_path).unwrap();
+        assert!(text.contains("CERTIFICATE OF SANITIZATION"));
+        assert!(text.contains("/tmp/b.log"));
+        assert!(text.contains("NOT VERIFIED"));
+    }
+}
@@ -7,23 +7,80 @@ use prettytable::{Table, Row, Cell, row};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::ErrorKind;
 use rayon::prelude::*;
-use chrono::{DateTime, Utc, Duration};
+use chrono::Utc;
 use std::time::SystemTime;
 use std::time::Duration as StdDuration;
 use std::thread;
 use colored::*;
-
-fn is_permission_denied(err: &WalkDirError) -> bool {
+use crate::age::AgeRule;
+use crate::certificate::{self, SanitizationCertificate, SanitizedFile};
+use crate::duplicates::{self, DuplicateGroup, DuplicateOptions, DuplicateTreeGroup, TreeEntry};
+use crate::filter;
+use crate::mounts;
+use crate::open_files::{self, OpenFiles};
+use crate::protect;
+use crate::safe_delete;
+use crate::size;
+
+pub(crate) fn is_permission_denied(err: &WalkDirError) -> bool {
     if let Some(inner_err) = err.io_error() {
         return inner_err.kind() == ErrorKind::PermissionDenied;
     }
     false
 }
 
-fn has_permission(path: &Path) -> bool {
+pub(crate) fn has_permission(path: &Path) -> bool {
     fs::read_dir(path).is_ok()
 }
 
+/// Returns the status for a file held open by another process, which is left
+/// in place so that the program using it does not break.
+pub(crate) fn in_use_status(open: Option<&OpenFiles>, path: &Path) -> Option<String> {
+    let holders = open_files::describe(open?.holders_of(path)?);
+    eprintln!("{}", format!("Skipping {}: open by {}", path.display(), holders).yellow());
+    Some(format!("In use by {}", holders))
+}
+
+/// Records a file left in place because it is in use, so that the
+/// sanitization certificate lists it as not sanitized.
+fn skipped_in_use(path: &Path, status: &str) -> SanitizedFile {
+    let holders = status.strip_prefix("In use by ").unwrap_or(status);
+    let size = fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0);
+    let e = std::io::Error::other(format!("skipped: in use by {}", holders));
+    SanitizedFile::failed(path, size, None, 0, &e)
+}
+
+/// Returns the status for a directory holding a file open in another
+/// process; the whole tree is then left in place.
+fn in_use_tree_status(open: Option<&OpenFiles>, dir: &Path) -> Option<String> {
+    let open = open?;
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .find_map(|entry| in_use_status(Some(open), entry.path()))
+}
+
+/// Reports a failed deletion and returns the status shown in the table.
+pub(crate) fn deletion_failure(path: &Path, e: &std::io::Error) -> String {
+    if protect::is_protected(e) {
+        eprintln!("{}", format!("Refusing to delete: {}", e).red());
+        "Protected".to_string()
+    } else if safe_delete::is_not_scanned(e) {
+        eprintln!("{}", format!("Not found by a scan, not deleted: {}", path.display()).yellow());
+        "Not scanned".to_string()
+    } else if safe_delete::is_changed_since_scan(e) {
+        eprintln!("{}", format!("Changed since scan, not deleted: {}", path.display()).yellow());
+        "Changed since scan".to_string()
+    } else if e.kind() == ErrorKind::PermissionDenied {
+        eprintln!("{}", format!("Permission denied: {}", path.display()).red());
+        "Permission denied".to_string()
+    } else {
+        eprintln!("{}", format!("Failed to delete {}: {}", path.display(), e).red());
+        "Failed to delete".to_string()
+    }
+}
+
 fn get_trash_dir() -> Option<PathBuf> {
     #[cfg(target_os = "windows")]
     {
@@ -39,8 +96,15 @@ fn get_trash_dir() -> Option<PathBuf> {
     }
 }
 
+/// Whether the cleaning rules select the file at `path`.
+pub fn is_cleanable(path: &Path, exclude_types: &HashSet<String>) -> bool {
+    static RE: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r".*\.(tmp|log|old|bak)$").unwrap());
+    let ext = path.extension().unwrap_or_default().to_str().unwrap_or_default();
+    re.is_match(path.to_str().unwrap_or_default()) && !exclude_types.contains(ext)
+}
+
 pub fn scan_files<P: AsRef<Path>>(dirs_to_scan: &[P], _exclude_dirs: &HashSet<String>, exclude_types: &HashSet<String>) -> Vec<PathBuf> {
-    let re = Regex::new(r".*\.(tmp|log|old|bak)$").unwrap();
     let mut table = Table::new();
     let mut files_to_clean = Vec::new();
 
@@ -52,7 +116,7 @@ pub fn scan_files<P: AsRef<Path>>(dirs_to_scan: &[P], _exclude_dirs: &HashSet<St
                 eprintln!("{}", format!("Skipping directory due to lack of permission: {}", dir.as_ref().display()).yellow());
                 vec![]
             } else {
-                WalkDir::new(dir).into_iter().collect::<Vec<_>>()
+                mounts::walk(WalkDir::new(dir)).collect::<Vec<_>>()
             }
         })
         .collect();
@@ -71,8 +135,8 @@ pub fn scan_files<P: AsRef<Path>>(dirs_to_scan: &[P], _exclude_dirs: &HashSet<St
             Ok(entry) => {
                 if entry.file_type().is_file() {
                     let path = entry.path();
-                    let ext = path.extension().unwrap_or_default().to_str().unwrap_or_default();
-                    if re.is_match(path.to_str().unwrap()) && !exclude_types.contains(ext) {
+                    if is_cleanable(path, exclude_types) && entry.metadata().is_ok_and(|m| size::accepts(&m) && filter::accepts(path, &m)) {
+                        safe_delete::record_entry(&entry);
                         files_to_clean.push(path.to_path_buf());
                         table.add_row(Row::new(vec![
                             Cell::new(&path.to_str().unwrap().blue().to_string()),
@@ -107,63 +171,70 @@ pub fn clean_files(files_to_clean: Vec<PathBuf>, secure: bool) {
         .progress_chars("█  "));
     pb.set_message("Cleaning in progress...");
 
-    let results: Vec<(String, String)> = files_to_clean.par_iter().map(|path| {
+    let started_at = Utc::now();
+    let open_files = OpenFiles::scan_if_enabled();
+    let results: Vec<(String, String, Option<SanitizedFile>)> = files_to_clean.par_iter().map(|path| {
         pb.inc(1);
         thread::sleep(StdDuration::from_millis(10));
-        let status = if secure {
-            match secure_delete(path) {
-                Ok(_) => "Deleted".to_string(),
-                Err(e) => {
-                    if e.kind() == ErrorKind::PermissionDenied {
-                        eprintln!("{}", format!("Permission denied: {}", path.display()).red());
-                        "Permission denied".to_string()
-                    } else {
-                        eprintln!("{}", format!("Failed to delete {}: {}", path.display(), e).red());
-                        "Failed to delete".to_string()
-                    }
-                }
-            }
-        } else {
-            match fs::remove_file(path) {
-                Ok(_) => "Deleted".to_string(),
-                Err(e) => {
-                    if e.kind() == ErrorKind::PermissionDenied {
-                        eprintln!("{}", format!("Permission denied: {}", path.display()).red());
-                        "Permission denied".to_string()
-                    } else {
-                        eprintln!("{}", format!("Failed to delete {}: {}", path.display(), e).red());
-                        "Failed to delete".to_string()
-                    }
-                }
-            }
+        if let Some(status) = in_use_status(open_files.as_ref(), path) {
+            let record = secure.then(|| skipped_in_use(path, &status));
+            return (path.to_str().unwrap().to_string(), status, record);
+        }
+        if secure {
+            let (status, record) = match sanitize(path) {
+                Ok(record) => ("Deleted".to_string(), record),
+                Err((record, e)) => (deletion_failure(path, &e), record),
+            };
+            return (path.to_str().unwrap().to_string(), status, Some(record));
+        }
+        let status = match safe_delete::remove_file(path) {
+            Ok(_) => "Deleted".to_string(),
+            Err(e) => deletion_failure(path, &e),
         };
-        (path.to_str().unwrap().to_string(), status)
+        (path.to_str().unwrap().to_string(), status, None)
     }).collect();
 
     pb.finish_with_message("Clean complete");
 
-    for (path, status) in results {
+    let mut records = Vec::new();
+    for (path, status, record) in results {
         table.add_row(Row::new(vec![
             Cell::new(&path.blue().to_string()),
             Cell::new(&status.green().to_string()),
         ]));
+        records.extend(record);
     }
 
     table.printstd();
+
+    if secure {
+        certificate::issue(records, SECURE_DELETE_PASSES, started_at);
+    }
 }
 
-pub fn clear_directory<P: AsRef<Path>>(dir: P) {
+/// Deletes the files under `dir` and returns those that were deleted.
+pub fn clear_directory<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    // Files directly inside e.g. the home directory are not protected one by
+    // one, so clearing a protected directory is refused as a whole.
+    if let Err(e) = protect::check(dir.as_ref()) {
+        eprintln!("{}", format!("Refusing to clear directory: {}", e).red());
+        return Vec::new();
+    }
     let mut table = Table::new();
     table.add_row(row!["File Path".bold().blue(), "Status".bold().blue()]);
+    let mut deleted = Vec::new();
 
     if has_permission(dir.as_ref()) {
-        let entries: Vec<_> = WalkDir::new(&dir).into_iter().collect();
+        let entries: Vec<_> = mounts::walk(WalkDir::new(&dir))
+            .inspect(|entry| if let Ok(entry) = entry { safe_delete::record_entry(entry) })
+            .collect();
         let pb = ProgressBar::new(entries.len() as u64);
         pb.set_style(ProgressStyle::default_bar()
             .template("{spinner:.green} {msg}\n{wide_bar:.green} {pos}/{len} ({percent}%)")
             .expect("Invalid template")
             .progress_chars("█  "));
         pb.set_message("Clearing directory...");
+        let open_files = OpenFiles::scan_if_enabled();
 
         let results: Vec<(String, String)> = entries.par_iter().map(|entry| {
             pb.inc(1);
@@ -172,17 +243,12 @@ pub fn clear_directory<P: AsRef<Path>>(dir: P) {
                 Ok(entry) => {
                     if entry.file_type().is_file() {
                         let path = entry.path();
-                        let status = match fs::remove_file(path) {
+                        if let Some(status) = in_use_status(open_files.as_ref(), path) {
+                            return (path.to_str().unwrap().to_string(), status);
+                        }
+                        let status = match safe_delete::remove_file(path) {
                             Ok(_) => "Deleted".to_string(),
-                            Err(e) => {
-                                if e.kind() == ErrorKind::PermissionDenied {
-                                    eprintln!("{}", format!("Permission denied: {}", path.display()).red());
-                                    "Permission denied".to_string()
-                                } else {
-                                    eprintln!("{}", format!("Failed to delete {}: {}", path.display(), e).red());
-                                    "Failed to delete".to_string()
-                                }
-                            }
+                            Err(e) => deletion_failure(path, &e),
                         };
                         (path.to_str().unwrap().to_string(), status)
                     } else {
@@ -190,7 +256,7 @@ pub fn clear_directory<P: AsRef<Path>>(dir: P) {
                     }
                 }
                 Err(e) => {
-                    if is_permission_denied(&e) {
+                    if is_permission_denied(e) {
                         eprintln!("{}", format!("Permission denied: {}", e).red());
                     } else {
                         eprintln!("{}", format!("Failed to access entry: {}", e).red());
@@ -203,6 +269,9 @@ pub fn clear_directory<P: AsRef<Path>>(dir: P) {
         pb.finish_with_message("Clear complete");
 
         for (path, status) in results {
+            if status == "Deleted" {
+                deleted.push(PathBuf::from(&path));
+            }
             table.add_row(Row::new(vec![
                 Cell::new(&path.blue().to_string()),
                 Cell::new(&status.green().to_string()),
@@ -212,6 +281,159 @@ pub fn clear_directory<P: AsRef<Path>>(dir: P) {
         eprintln!("{}", format!("Skipping directory due to lack of permission: {}", dir.as_ref().display()).yellow());
     }
 
+    table.printstd();
+    deleted
+}
+
+/// Removes the directories left empty under `dirs`, deepest first, so that
+/// directories emptied by the removal of their sub-directories go too. The
+/// roots themselves and excluded directories are never removed.
+pub fn remove_empty_dirs<P: AsRef<Path>>(dirs: &[P], exclude_dirs: &HashSet<String>) -> Vec<PathBuf> {
+    let mut table = Table::new();
+    table.add_row(row!["Directory".bold().blue(), "Status".bold().blue()]);
+    let mut removed = Vec::new();
+
+    for dir in dirs {
+        if !has_permission(dir.as_ref()) {
+            eprintln!("{}", format!("Skipping directory due to lack of permission: {}", dir.as_ref().display()).yellow());
+            continue;
+        }
+        // Collect first and remove deepest first, so that a parent emptied by
+        // removing its children is removed as well.
+        let mut candidates = Vec::new();
+        let walker = mounts::walk_filtered(WalkDir::new(dir).min_depth(1), |e| {
+            !exclude_dirs.contains(e.path().to_str().unwrap_or_default())
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if entry.file_type().is_dir() {
+                        safe_delete::record_entry(&entry);
+                        candidates.push((entry.depth(), entry.into_path()));
+                    }
+                }
+                Err(e) => {
+                    if is_permission_denied(&e) {
+                        eprintln!("{}", format!("Permission denied: {}", e).red());
+                    } else {
+                        eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                    }
+                }
+            }
+        }
+        candidates.sort_by_key(|(depth, _)| std::cmp::Reverse(*depth));
+
+        for (_, path) in candidates {
+            // remove_dir only succeeds on empty directories, so there is
+            // no window in which a file could be lost.
+            match safe_delete::remove_dir(&path) {
+                Ok(_) => {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&path.to_str().unwrap().blue().to_string()),
+                        Cell::new(&"Deleted".green().to_string()),
+                    ]));
+                    removed.push(path);
+                }
+                Err(e) if e.kind() == ErrorKind::DirectoryNotEmpty => {}
+                Err(e) => {
+                    deletion_failure(&path, &e);
+                }
+            }
+        }
+    }
+
+    if removed.is_empty() {
+        println!("{}", "No empty directories found.".green());
+    } else {
+        table.printstd();
+        println!("{}", format!("Empty directories removed: {}", removed.len()).green());
+    }
+    removed
+}
+
+/// Removes the directories that a clean left empty: starting from the parent
+/// of each path in `deleted` that is gone, walks up while the directory is
+/// empty, stopping at the scan root, which is never removed. Directories that
+/// were already empty elsewhere under the roots are left alone.
+pub fn remove_emptied_dirs<P: AsRef<Path>>(deleted: &[PathBuf], roots: &[P], exclude_dirs: &HashSet<String>) -> Vec<PathBuf> {
+    let mut table = Table::new();
+    table.add_row(row!["Directory".bold().blue(), "Status".bold().blue()]);
+    let mut removed = Vec::new();
+
+    let mut candidates: Vec<&Path> = deleted.iter()
+        .filter(|path| fs::symlink_metadata(path).is_err())
+        .filter_map(|path| path.parent())
+        .collect();
+    // Deepest first, so that a parent is tried after its children.
+    candidates.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    candidates.dedup();
+
+    for start in candidates {
+        let Some(root) = roots.iter().map(|r| r.as_ref()).filter(|r| start.starts_with(r)).max_by_key(|r| r.components().count()) else {
+            continue;
+        };
+        let mut dir = start;
+        while dir != root && dir.starts_with(root) && !exclude_dirs.contains(dir.to_str().unwrap_or_default()) {
+            // remove_dir only succeeds on empty directories.
+            match safe_delete::remove_dir(dir) {
+                Ok(_) => {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&dir.to_str().unwrap_or_default().blue().to_string()),
+                        Cell::new(&"Deleted".green().to_string()),
+                    ]));
+                    removed.push(dir.to_path_buf());
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) if e.kind() == ErrorKind::DirectoryNotEmpty => break,
+                Err(e) => {
+                    deletion_failure(dir, &e);
+                    break;
+                }
+            }
+            let Some(parent) = dir.parent() else { break };
+            dir = parent;
+        }
+    }
+
+    if !removed.is_empty() {
+        table.printstd();
+        println!("{}", format!("Empty directories removed: {}", removed.len()).green());
+    }
+    removed
+}
+
+/// Moves `files_to_trash` (files or whole directories) to the desktop trash
+/// so that they can still be restored.
+pub fn trash_files(files_to_trash: Vec<PathBuf>) {
+    let mut table = Table::new();
+    table.add_row(row!["File Path".bold().blue(), "Status".bold().blue()]);
+    let open_files = OpenFiles::scan_if_enabled();
+
+    for path in &files_to_trash {
+        let in_use = if fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
+            in_use_tree_status(open_files.as_ref(), path)
+        } else {
+            in_use_status(open_files.as_ref(), path)
+        };
+        let status = if let Some(status) = in_use {
+            status
+        } else if let Err(e) = protect::check(path) {
+            deletion_failure(path, &e)
+        } else {
+            match trash::delete(path) {
+                Ok(_) => "Moved to trash".to_string(),
+                Err(e) => {
+                    eprintln!("{}", format!("Failed to move {} to trash: {}", path.display(), e).red());
+                    "Failed to move to trash".to_string()
+                }
+            }
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&path.to_str().unwrap().blue().to_string()),
+            Cell::new(&status.green().to_string()),
+        ]));
+    }
+
     table.printstd();
 }
 
@@ -236,32 +458,136 @@ pub fn report_clean(files_to_clean: Vec<PathBuf>, start_time: SystemTime) {
     println!("{}", format!("Time taken: {:.2?}", duration).green());
 }
 
-pub fn secure_delete(path: &Path) -> std::io::Result<()> {
+pub const SECURE_DELETE_PASSES: u32 = 1;
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+    let mut file = safe_delete::open_file(path, false)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Overwrites the first `len` bytes of `path` with random data and returns
+/// the SHA-256 of the final pass so it can be checked on read-back.
+fn overwrite_file(path: &Path, len: u64) -> std::io::Result<String> {
     use rand::Rng;
-    let metadata = fs::metadata(path)?;
-    let len = metadata.len();
+    use sha2::{Digest, Sha256};
+    use std::io::{Seek, SeekFrom, Write};
+
+    let mut file = safe_delete::open_file(path, true)?;
     let mut rng = rand::thread_rng();
-    let mut data = vec![0u8; len as usize];
-    rng.fill(&mut data[..]);
-    fs::write(path, &data)?;
-    fs::remove_file(path)
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut written_hash = String::new();
+    for _ in 0..SECURE_DELETE_PASSES {
+        let mut hasher = Sha256::new();
+        let mut remaining = len;
+        file.seek(SeekFrom::Start(0))?;
+        while remaining > 0 {
+            let chunk = remaining.min(buffer.len() as u64) as usize;
+            rng.fill(&mut buffer[..chunk]);
+            file.write_all(&buffer[..chunk])?;
+            hasher.update(&buffer[..chunk]);
+            remaining -= chunk as u64;
+        }
+        file.sync_all()?;
+        written_hash = format!("{:x}", hasher.finalize());
+    }
+    Ok(written_hash)
+}
+
+/// Overwrites `path` in place, verifies the last pass by reading it back and
+/// unlinks it, returning what happened for the sanitization certificate.
+pub fn sanitize_file(path: &Path) -> SanitizedFile {
+    sanitize(path).unwrap_or_else(|(record, _)| record)
+}
+
+/// Number of names the file at `path` has; 1 where hard links are unknown.
+#[cfg(unix)]
+fn hard_link_count(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path).map(|m| m.nlink()).unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn hard_link_count(_path: &Path) -> u64 {
+    1
+}
+
+/// Sanitizes `path`, returning the error of a failed run with its record so
+/// that the caller can report it like any other failed deletion.
+fn sanitize(path: &Path) -> Result<SanitizedFile, (SanitizedFile, std::io::Error)> {
+    // A symbolic link holds no data of its own, and overwriting through it
+    // would wipe whatever it points to, possibly outside the scanned tree.
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+        return match safe_delete::remove_file(path) {
+            Ok(()) => Ok(SanitizedFile {
+                path: path.display().to_string(),
+                size: 0,
+                sha256_before: None,
+                passes: 0,
+                verified: true,
+                deleted: true,
+                error: None,
+            }),
+            Err(e) => Err((SanitizedFile::failed(path, 0, None, 0, &e), e)),
+        };
+    }
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) => return Err((SanitizedFile::failed(path, 0, None, 0, &e), e)),
+    };
+    // The data of a file with other hard links is also theirs, possibly
+    // outside the scanned tree; overwriting it would wipe them too.
+    let other_links = hard_link_count(path).saturating_sub(1);
+    if other_links > 0 {
+        let e = std::io::Error::other(format!("has {} other hard links; overwrite would change them", other_links));
+        return Err((SanitizedFile::failed(path, len, None, 0, &e), e));
+    }
+    let sha256_before = match sha256_file(path) {
+        Ok(hash) => hash,
+        Err(e) => return Err((SanitizedFile::failed(path, len, None, 0, &e), e)),
+    };
+    let written_hash = match overwrite_file(path, len) {
+        Ok(hash) => hash,
+        Err(e) => return Err((SanitizedFile::failed(path, len, Some(sha256_before), SECURE_DELETE_PASSES, &e), e)),
+    };
+
+    let verified = match sha256_file(path) {
+        Ok(hash) => hash == written_hash,
+        Err(e) => return Err((SanitizedFile::failed(path, len, Some(sha256_before), SECURE_DELETE_PASSES, &e), e)),
+    };
+    if let Err(e) = safe_delete::remove_file(path) {
+        let mut record = SanitizedFile::failed(path, len, Some(sha256_before), SECURE_DELETE_PASSES, &e);
+        record.verified = verified;
+        return Err((record, e));
+    }
+
+    Ok(SanitizedFile {
+        path: path.display().to_string(),
+        size: len,
+        sha256_before: Some(sha256_before),
+        passes: SECURE_DELETE_PASSES,
+        verified,
+        deleted: true,
+        error: None,
+    })
 }
 
-pub fn scan_files_for_age<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>, age_in_days: i64) -> Vec<PathBuf> {
+pub fn scan_files_for_age<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>, rule: &AgeRule) -> Vec<PathBuf> {
     let now = Utc::now();
     let mut files_to_clean = Vec::new();
 
     for dir in dirs_to_scan {
         let dir_str = dir.as_ref().to_str().unwrap();
         if !exclude_dirs.contains(dir_str) && has_permission(dir.as_ref()) {
-            for entry in WalkDir::new(dir) {
+            for entry in mounts::walk(WalkDir::new(dir)) {
                 match entry {
                     Ok(entry) => {
                         if entry.file_type().is_file() {
-                            let metadata = fs::metadata(entry.path()).unwrap();
-                            if let Ok(modified) = metadata.modified() {
-                                let modified: DateTime<Utc> = modified.into();
-                                if now.signed_duration_since(modified) > Duration::days(age_in_days) {
+                            if let Ok(metadata) = entry.metadata() {
+                                if rule.matches(&metadata, now) && size::accepts(&metadata) && filter::accepts(entry.path(), &metadata) {
+                                    safe_delete::record_walked(&entry, &metadata);
                                     files_to_clean.push(entry.path().to_path_buf());
                                 }
                             }
@@ -284,25 +610,21 @@ pub fn scan_files_for_age<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &Has
     files_to_clean
 }
 
-pub fn find_duplicate_files(dirs_to_scan: &[PathBuf], exclude_dirs: &HashSet<String>, exclude_types: &HashSet<String>) -> Vec<PathBuf> {
-    let mut files_to_clean = Vec::new();
-    let mut file_hashes: HashSet<u64> = HashSet::new();
+pub fn find_duplicate_files(dirs_to_scan: &[PathBuf], exclude_dirs: &HashSet<String>, exclude_types: &HashSet<String>, options: &DuplicateOptions) -> Vec<DuplicateGroup> {
+    let mut candidates = Vec::new();
 
     for dir in dirs_to_scan {
         if !exclude_dirs.contains(dir.to_str().unwrap()) && has_permission(dir.as_ref()) {
-            for entry in WalkDir::new(dir) {
+            for entry in mounts::walk(WalkDir::new(dir)) {
                 match entry {
                     Ok(entry) => {
                         if entry.file_type().is_file() {
                             let path = entry.path();
                             let ext = path.extension().unwrap_or_default().to_str().unwrap_or_default();
                             if !exclude_types.contains(ext) {
-                                let metadata = fs::metadata(path).unwrap();
-                                let hash = metadata.len();
-                                if file_hashes.contains(&hash) {
-                                    files_to_clean.push(path.to_path_buf());
-                                } else {
-                                    file_hashes.insert(hash);
+                                if let Some(metadata) = entry.metadata().ok().filter(|m| size::accepts(m) && filter::accepts(path, m)) {
+                                    safe_delete::record_walked(&entry, &metadata);
+                                    candidates.push((path.to_path_buf(), metadata.len()));
                                 }
                             }
                         }
@@ -319,33 +641,125 @@ pub fn find_duplicate_files(dirs_to_scan: &[PathBuf], exclude_dirs: &HashSet<Str
         }
     }
 
-    files_to_clean
+    duplicates::group_identical(candidates, options)
 }
 
-pub fn print_duplicates_report(duplicate_files: &[PathBuf]) {
+pub fn print_duplicates_report(duplicate_groups: &[DuplicateGroup]) {
     let mut table = Table::new();
-    table.add_row(row!["File Path".bold().blue(), "Status".bold().blue()]);
+    table.add_row(row!["Group".bold().blue(), "File Path".bold().blue(), "Size".bold().blue(), "Status".bold().blue()]);
 
-    for file in duplicate_files {
-        table.add_row(Row::new(vec![
-            Cell::new(&file.to_str().unwrap().blue().to_string()),
-            Cell::new(&"Duplicate".yellow().to_string()),
-        ]));
+    for (index, group) in duplicate_groups.iter().enumerate() {
+        for file in &group.files {
+            let status = if file.as_path() == group.keeper() {
+                "Keep".green().to_string()
+            } else {
+                "Duplicate".yellow().to_string()
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&(index + 1).to_string()),
+                Cell::new(&file.to_str().unwrap().blue().to_string()),
+                Cell::new(&format!("{} bytes", group.size)),
+                Cell::new(&status),
+            ]));
+        }
     }
 
     table.printstd();
+
+    let reclaimable: u64 = duplicate_groups.iter().map(|g| g.reclaimable()).sum();
+    println!("{}", format!("Duplicate groups: {}", duplicate_groups.len()).green());
+    println!("{}", format!("Reclaimable space: {} bytes", reclaimable).green());
+}
+
+pub fn find_duplicate_trees(dirs_to_scan: &[PathBuf], exclude_dirs: &HashSet<String>, options: &DuplicateOptions) -> Vec<DuplicateTreeGroup> {
+    let mut entries = Vec::new();
+
+    for dir in dirs_to_scan {
+        if !exclude_dirs.contains(dir.to_str().unwrap()) && has_permission(dir.as_ref()) {
+            for entry in mounts::walk(WalkDir::new(dir)) {
+                match entry {
+                    Ok(entry) => {
+                        let path = entry.path().to_path_buf();
+                        let file_type = entry.file_type();
+                        if file_type.is_dir() {
+                            safe_delete::record_entry(&entry);
+                            entries.push(TreeEntry::Dir(path));
+                        } else if file_type.is_symlink() {
+                            let target = fs::read_link(&path).unwrap_or_default();
+                            entries.push(TreeEntry::Symlink(path, target));
+                        } else if let Ok(metadata) = entry.metadata() {
+                            entries.push(TreeEntry::File(path, metadata.len()));
+                        } else {
+                            entries.push(TreeEntry::Unreadable(entry.path().parent().unwrap_or(dir).to_path_buf()));
+                        }
+                    }
+                    Err(e) => {
+                        if let Some(path) = e.path() {
+                            entries.push(TreeEntry::Unreadable(path.to_path_buf()));
+                        }
+                        if is_permission_denied(&e) {
+                            eprintln!("{}", format!("Permission denied: {}", e).red());
+                        } else {
+                            eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    duplicates::group_duplicate_trees(dirs_to_scan, entries, options)
 }
 
-pub fn clean_browser_cache() {
-    let cache_dirs = vec![
-        dirs::cache_dir().unwrap().join("mozilla"),
-        dirs::cache_dir().unwrap().join("google-chrome"),
-        dirs::cache_dir().unwrap().join("firefox"),
-    ];
+pub fn print_duplicate_trees_report(tree_groups: &[DuplicateTreeGroup]) {
+    let mut table = Table::new();
+    table.add_row(row!["Group".bold().blue(), "Directory".bold().blue(), "Files".bold().blue(), "Size".bold().blue(), "Status".bold().blue()]);
 
-    for cache_dir in cache_dirs {
-        clear_directory(cache_dir);
+    for (index, group) in tree_groups.iter().enumerate() {
+        for dir in &group.dirs {
+            let status = if dir.as_path() == group.keeper() {
+                "Keep".green().to_string()
+            } else {
+                "Duplicate tree".yellow().to_string()
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&(index + 1).to_string()),
+                Cell::new(&dir.to_str().unwrap().blue().to_string()),
+                Cell::new(&group.files.to_string()),
+                Cell::new(&format!("{} bytes", group.size)),
+                Cell::new(&status),
+            ]));
+        }
     }
+
+    table.printstd();
+
+    let reclaimable: u64 = tree_groups.iter().map(|g| g.reclaimable()).sum();
+    println!("{}", format!("Duplicate directory trees: {}", tree_groups.len()).green());
+    println!("{}", format!("Reclaimable space: {} bytes", reclaimable).green());
+}
+
+pub fn remove_duplicate_trees(tree_groups: &[DuplicateTreeGroup]) {
+    let mut table = Table::new();
+    table.add_row(row!["Directory".bold().blue(), "Status".bold().blue()]);
+
+    let open_files = OpenFiles::scan_if_enabled();
+    for dir in tree_groups.iter().flat_map(|g| g.redundant()) {
+        let status = if let Some(status) = in_use_tree_status(open_files.as_ref(), dir) {
+            status
+        } else {
+            match safe_delete::remove_dir_all(dir) {
+                Ok(_) => "Deleted".to_string(),
+                Err(e) => deletion_failure(dir, &e),
+            }
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&dir.to_str().unwrap().blue().to_string()),
+            Cell::new(&status.green().to_string()),
+        ]));
+    }
+
+    table.printstd();
 }
 
 pub fn restore_files() {
@@ -353,19 +767,30 @@ pub fn restore_files() {
     println!("Restoring files...");
 }
 
-pub fn secure_clean_files(files_to_clean: Vec<PathBuf>) {
+pub fn secure_clean_files(files_to_clean: Vec<PathBuf>) -> SanitizationCertificate {
+    let started_at = Utc::now();
+    let mut records = Vec::new();
+    let open_files = OpenFiles::scan_if_enabled();
     for file in files_to_clean {
-        match secure_delete(&file) {
-            Ok(_) => println!("Securely deleted: {}", file.display()),
-            Err(e) => println!("Failed to securely delete {}: {}", file.display(), e),
+        if let Some(status) = in_use_status(open_files.as_ref(), &file) {
+            records.push(skipped_in_use(&file, &status));
+            continue;
+        }
+        let record = sanitize_file(&file);
+        match &record.error {
+            None => println!("Securely deleted: {}", file.display()),
+            Some(e) => println!("Failed to securely delete {}: {}", file.display(), e),
         }
+        records.push(record);
     }
+    certificate::issue(records, SECURE_DELETE_PASSES, started_at)
 }
 
 // Tests Unitaires
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::age::TimeField;
     use std::fs::{File};
     use tempfile::tempdir;
 
@@ -402,7 +827,12 @@ mod tests {
         File::create(&file2).unwrap();
         File::create(&file3).unwrap();
 
-        let files_to_clean = vec![file1.clone(), file2.clone()];
+        let files_to_clean = scan_files(&[dir.path()], &HashSet::new(), &HashSet::new());
+        assert_eq!(files_to_clean.len(), 2);
+
+        // Files that no scan reported are left alone.
+        clean_files(vec![file3.clone()], false);
+        assert!(file3.exists());
 
         clean_files(files_to_clean.clone(), false);
 
@@ -433,6 +863,61 @@ mod tests {
         assert!(!file3.exists());
     }
 
+    #[test]
+    fn test_sanitize_file_records_hash_and_verification() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("secret.log");
+        fs::write(&file, "duplicate content").unwrap();
+        safe_delete::record(&file, &fs::symlink_metadata(&file).unwrap());
+
+        let record = sanitize_file(&file);
+
+        assert!(!file.exists());
+        assert!(record.deleted);
+        assert!(record.verified);
+        assert_eq!(record.size, 17);
+        assert_eq!(record.passes, SECURE_DELETE_PASSES);
+        assert_eq!(
+            record.sha256_before.as_deref(),
+            Some("b79f8c07798dcc75d6f288e6a620644a88a9c67e74019a57b88a5bfd918e4b0f")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sanitize_file_unlinks_symlink_without_touching_target() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.log");
+        fs::write(&target, "keep me").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        safe_delete::record(&link, &fs::symlink_metadata(&link).unwrap());
+
+        let record = sanitize_file(&link);
+
+        assert!(record.deleted);
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "keep me");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sanitize_file_refuses_file_with_other_hard_links() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("report.log");
+        let other = dir.path().join("elsewhere.txt");
+        fs::write(&file, "shared data").unwrap();
+        fs::hard_link(&file, &other).unwrap();
+        safe_delete::record(&file, &fs::symlink_metadata(&file).unwrap());
+
+        let record = sanitize_file(&file);
+
+        assert!(!record.deleted);
+        assert!(record.error.unwrap().contains("1 other hard links"));
+        assert!(file.exists());
+        assert_eq!(fs::read_to_string(&other).unwrap(), "shared data");
+    }
+
     #[test]
     fn test_scan_files_for_age() {
         let dir = tempdir().unwrap();
@@ -453,7 +938,8 @@ mod tests {
         filetime::set_file_mtime(&file1, filetime::FileTime::from_system_time(modified_time)).unwrap();
         filetime::set_file_mtime(&file2, filetime::FileTime::from_system_time(modified_time)).unwrap();
 
-        let files_to_clean = scan_files_for_age(&dirs_to_scan, &exclude_dirs, age_in_days as i64);
+        let rule = AgeRule { field: TimeField::Modified, older_than: Some(chrono::Duration::days(age_in_days as i64)), newer_than: None };
+        let files_to_clean = scan_files_for_age(&dirs_to_scan, &exclude_dirs, &rule);
 
         assert!(files_to_clean.contains(&file1));
         assert!(files_to_clean.contains(&file2));
@@ -479,10 +965,150 @@ mod tests {
         fs::write(&file1, "duplicate content").unwrap();
         fs::write(&file2, "duplicate content").unwrap();
 
-        let duplicate_files = find_duplicate_files(&dirs_to_scan, &exclude_dirs, &exclude_types);
+        let duplicate_groups = find_duplicate_files(&dirs_to_scan, &exclude_dirs, &exclude_types, &DuplicateOptions::default());
+
+        assert_eq!(duplicate_groups.len(), 1);
+        let group = &duplicate_groups[0];
+        assert!(group.files.contains(&file1));
+        assert!(group.files.contains(&file2));
+        assert!(!group.files.contains(&file3));
+        // Only one copy of the identical pair is queued for deletion.
+        assert_eq!(group.redundant().count(), 1);
+    }
 
-        assert!(duplicate_files.contains(&file1));
-        assert!(duplicate_files.contains(&file2));
-        assert!(!duplicate_files.contains(&file3));
+    #[test]
+    fn test_nested_scan_roots_do_not_pair_a_file_with_itself() {
+        let dir = tempdir().unwrap();
+        let downloads = dir.path().join("Downloads");
+        fs::create_dir_all(&downloads).unwrap();
+        let only = downloads.join("only.bin");
+        fs::write(&only, "the only copy").unwrap();
+
+        let dirs_to_scan = vec![dir.path().to_path_buf(), downloads.clone()];
+        let groups = find_duplicate_files(&dirs_to_scan, &HashSet::new(), &HashSet::new(), &DuplicateOptions::default());
+        assert!(groups.is_empty());
+
+        // A real copy is paired once, with one path to delete.
+        let copy = dir.path().join("copy.bin");
+        fs::write(&copy, "the only copy").unwrap();
+        let groups = find_duplicate_files(&dirs_to_scan, &HashSet::new(), &HashSet::new(), &DuplicateOptions::default());
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].redundant().count(), 1);
+        assert!(groups[0].redundant().all(|p| p.as_path() != groups[0].keeper()));
+    }
+
+    #[test]
+    fn test_remove_duplicate_trees() {
+        let dir = tempdir().unwrap();
+        let photos = dir.path().join("Photos");
+        let copy = dir.path().join("Photos (copy)");
+        for tree in [&photos, &copy] {
+            fs::create_dir_all(tree.join("2023")).unwrap();
+            fs::write(tree.join("2023").join("img.jpg"), "jpeg bytes").unwrap();
+        }
+
+        let exclude_dirs: HashSet<String> = HashSet::new();
+        let dirs_to_scan = vec![dir.path().to_path_buf()];
+        let tree_groups = find_duplicate_trees(&dirs_to_scan, &exclude_dirs, &DuplicateOptions::default());
+
+        assert_eq!(tree_groups.len(), 1);
+        remove_duplicate_trees(&tree_groups);
+        assert_ne!(photos.exists(), copy.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_remove_duplicate_trees_keeps_tree_with_open_file() {
+        let dir = tempdir().unwrap();
+        let photos = dir.path().join("Photos");
+        let copy = dir.path().join("Photos (copy)");
+        for tree in [&photos, &copy] {
+            fs::create_dir_all(tree.join("2023")).unwrap();
+            fs::write(tree.join("2023").join("img.jpg"), "jpeg bytes").unwrap();
+        }
+        let tree_groups = find_duplicate_trees(&[dir.path().to_path_buf()], &HashSet::new(), &DuplicateOptions::default());
+        let redundant = tree_groups[0].redundant().next().unwrap().clone();
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .stdin(File::open(redundant.join("2023").join("img.jpg")).unwrap())
+            .spawn()
+            .unwrap();
+        remove_duplicate_trees(&tree_groups);
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert!(photos.exists() && copy.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_file_in_use_is_recorded_as_not_sanitized() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("app.log");
+        fs::write(&file, "log").unwrap();
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .stdin(File::open(&file).unwrap())
+            .spawn()
+            .unwrap();
+        let comm = PathBuf::from(format!("/proc/{}/comm", child.id()));
+        for _ in 0..100 {
+            if fs::read_to_string(&comm).is_ok_and(|c| c.trim_end() == "sleep") {
+                break;
+            }
+            thread::sleep(StdDuration::from_millis(10));
+        }
+        let open = OpenFiles::scan();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let status = in_use_status(Some(&open), &file).unwrap();
+        let record = skipped_in_use(&file, &status);
+        assert!(!record.deleted);
+        assert_eq!(record.error.as_deref(), Some(format!("skipped: in use by {} (sleep)", child.id()).as_str()));
+        let certificate = SanitizationCertificate::new(vec![record], SECURE_DELETE_PASSES, Utc::now());
+        assert_eq!(certificate.failed_files, 1);
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn test_remove_empty_dirs() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("a").join("b").join("c");
+        let kept = dir.path().join("kept");
+        let excluded = dir.path().join("excluded");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(&kept).unwrap();
+        fs::create_dir_all(&excluded).unwrap();
+        File::create(kept.join("file.txt")).unwrap();
+
+        let exclude_dirs: HashSet<String> = vec![excluded.to_str().unwrap().to_string()].into_iter().collect();
+        let removed = remove_empty_dirs(&[dir.path()], &exclude_dirs);
+
+        assert_eq!(removed.len(), 3);
+        assert!(!dir.path().join("a").exists());
+        assert!(kept.exists());
+        assert!(excluded.exists());
+        assert!(dir.path().exists());
+    }
+
+    #[test]
+    fn test_remove_emptied_dirs_only_walks_up_from_deleted_files() {
+        let dir = tempdir().unwrap();
+        let logs = dir.path().join("project").join("logs").join("old");
+        let tags = dir.path().join("project").join(".git").join("refs").join("tags");
+        fs::create_dir_all(&logs).unwrap();
+        fs::create_dir_all(&tags).unwrap();
+        fs::write(logs.join("a.log"), "log").unwrap();
+        fs::remove_file(logs.join("a.log")).unwrap();
+
+        let removed = remove_emptied_dirs(&[logs.join("a.log")], &[dir.path()], &HashSet::new());
+        assert_eq!(removed, vec![logs.clone(), dir.path().join("project").join("logs")]);
+        // Already empty before the clean, so not ours to remove.
+        assert!(tags.exists());
+        assert!(dir.path().join("project").exists());
     }
 }
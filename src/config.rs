@@ -0,0 +1,85 @@
+use crate::filter::Filter;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Paths added to or lifted from the built-in protected paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtectConfig {
+    /// Paths that are never deleted, together with everything below them.
+    pub deny: Vec<PathBuf>,
+    /// Paths that built-in rules would protect but that may be cleaned.
+    pub allow: Vec<PathBuf>,
+}
+
+/// Settings read from `config.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub protect: ProtectConfig,
+    /// Named filter expressions, used as `@name` in `--where`.
+    pub rules: BTreeMap<String, String>,
+}
+
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rustcleaner")
+        .join("config.json")
+}
+
+impl Config {
+    /// Loads the configuration at `path`; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Describes each invalid rule on its own; a bad rule only fails the
+    /// `--where` expressions that use it.
+    pub fn rule_errors(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter_map(|(name, expr)| Filter::parse(expr, &BTreeMap::new()).err().map(|e| format!("rule '{}': {}", name, e)))
+            .collect()
+    }
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_config() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        fs::write(&path, r#"{ "protect": { "deny": ["/data"] } }"#).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.protect.deny, vec![PathBuf::from("/data")]);
+        assert!(config.protect.allow.is_empty());
+
+        fs::write(&path, r#"{ "protect": { "denny": [] } }"#).unwrap();
+        assert!(Config::load(&path).unwrap_err().contains("denny"));
+
+        fs::write(&path, r#"{ "rules": { "old-logs": "ext = log and age > 30d" } }"#).unwrap();
+        assert_eq!(Config::load(&path).unwrap().rules["old-logs"], "ext = log and age > 30d");
+        fs::write(&path, r#"{ "protect": { "deny": ["/data"] }, "rules": { "old-logs": "ext = log and age >> 30d", "tmp": "ext = tmp" } }"#).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.protect.deny, vec![PathBuf::from("/data")]);
+        let errors = config.rule_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("rule 'old-logs'"));
+    }
+}
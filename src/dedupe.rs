@@ -0,0 +1,352 @@
+use crate::cleaner;
+use crate::duplicates::{self, DuplicateGroup};
+use crate::open_files::OpenFiles;
+use crate::protect;
+use colored::*;
+use filetime::FileTime;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{Table, Row, Cell, row};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// How redundant copies of a duplicate group are replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkMode {
+    /// Hard link to the kept copy; only possible on the same filesystem and
+    /// when both paths already have the same permissions, owner and
+    /// modification time, since they end up sharing one inode.
+    Hardlink,
+    /// Copy-on-write clone of the kept copy (`FICLONE`, btrfs/XFS); every path
+    /// keeps its own inode, permissions and timestamps.
+    Reflink,
+    /// Reflink when the filesystem supports it, hard link otherwise.
+    Auto,
+}
+
+#[derive(Debug)]
+pub struct LinkOutcome {
+    pub path: PathBuf,
+    pub status: String,
+    pub saved: u64,
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    use rand::Rng;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let suffix: u32 = rand::thread_rng().gen();
+    path.with_file_name(format!(".{}.rustcleaner-{:08x}", name, suffix))
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, dest: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    // _IOW(0x94, 9, int) from <linux/fs.h>
+    const FICLONE: libc::c_ulong = 0x4004_9409;
+
+    let src = fs::File::open(source)?;
+    let dst = fs::OpenOptions::new().write(true).create_new(true).mode(mode).open(dest)?;
+    if unsafe { libc::ioctl(dst.as_raw_fd(), FICLONE as _, src.as_raw_fd()) } != 0 {
+        let err = io::Error::last_os_error();
+        drop(dst);
+        let _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_source: &Path, _dest: &Path, _mode: u32) -> io::Result<()> {
+    Err(io::Error::new(ErrorKind::Unsupported, "reflinks are only supported on Linux"))
+}
+
+#[cfg(unix)]
+fn check_hardlink(keeper: &fs::Metadata, copy: &fs::Metadata) -> Result<(), &'static str> {
+    use std::os::unix::fs::MetadataExt;
+    if keeper.dev() != copy.dev() {
+        return Err("Different filesystem");
+    }
+    if keeper.mode() != copy.mode() || keeper.uid() != copy.uid() || keeper.gid() != copy.gid() {
+        return Err("Permissions differ");
+    }
+    if FileTime::from_last_modification_time(keeper) != FileTime::from_last_modification_time(copy) {
+        return Err("Modification times differ");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_hardlink(keeper: &fs::Metadata, copy: &fs::Metadata) -> Result<(), &'static str> {
+    if FileTime::from_last_modification_time(keeper) != FileTime::from_last_modification_time(copy) {
+        return Err("Modification times differ");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn same_inode(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_inode(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0o644
+}
+
+/// Gives the clone at `temp` the permissions, owner and timestamps of the
+/// file it is about to replace.
+fn restore_metadata(temp: &Path, original: &fs::Metadata) -> io::Result<()> {
+    fs::set_permissions(temp, original.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Changing the owner needs privileges; a clone owned by the current
+        // user is still correct for the current user's own files.
+        let _ = std::os::unix::fs::lchown(temp, Some(original.uid()), Some(original.gid()));
+    }
+    filetime::set_file_times(
+        temp,
+        FileTime::from_last_access_time(original),
+        FileTime::from_last_modification_time(original),
+    )
+}
+
+fn replace_with_reflink(keeper: &Path, path: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    let temp = temp_path_for(path);
+    reflink(keeper, &temp, file_mode(metadata))?;
+    let result = restore_metadata(&temp, metadata).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn replace_with_hardlink(keeper: &Path, path: &Path) -> io::Result<()> {
+    let temp = temp_path_for(path);
+    fs::hard_link(keeper, &temp)?;
+    let result = fs::rename(&temp, path);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn link_one(keeper: &Path, keeper_meta: &fs::Metadata, path: &Path, mode: LinkMode, open: Option<&OpenFiles>) -> LinkOutcome {
+    let outcome = |status: &str, saved: u64| LinkOutcome { path: path.to_path_buf(), status: status.to_string(), saved };
+
+    if let Err(e) = protect::check(path) {
+        eprintln!("{}", format!("Refusing to replace: {}", e).red());
+        return outcome("Protected", 0);
+    }
+    // A program writing to the copy would keep writing to the old inode.
+    if let Some(status) = cleaner::in_use_status(open, path) {
+        return outcome(&status, 0);
+    }
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(e) => return outcome(&format!("Failed: {}", e), 0),
+    };
+    if same_inode(keeper_meta, &metadata) {
+        return outcome("Already linked", 0);
+    }
+    // A copy edited since the scan, even at the same size, must not be
+    // replaced by the keeper's contents.
+    if metadata.len() != keeper_meta.len() {
+        return outcome("Changed since scan", 0);
+    }
+    match duplicates::same_contents(keeper, path) {
+        Ok(true) => {}
+        Ok(false) => return outcome("Changed since scan", 0),
+        Err(e) => return outcome(&format!("Failed: {}", e), 0),
+    }
+
+    if matches!(mode, LinkMode::Reflink | LinkMode::Auto) {
+        match replace_with_reflink(keeper, path, &metadata) {
+            Ok(()) => return outcome("Reflinked", metadata.len()),
+            Err(e) if mode == LinkMode::Reflink => return outcome(&format!("Reflink failed: {}", e), 0),
+            Err(_) => {}
+        }
+    }
+
+    if let Err(reason) = check_hardlink(keeper_meta, &metadata) {
+        return outcome(reason, 0);
+    }
+    match replace_with_hardlink(keeper, path) {
+        Ok(()) => outcome("Hardlinked", metadata.len()),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => outcome("Permission denied", 0),
+        Err(e) => outcome(&format!("Hardlink failed: {}", e), 0),
+    }
+}
+
+/// Replaces every redundant copy in `groups` with a link to the group's
+/// keeper so that all paths keep working, and reports what happened to each.
+pub fn link_duplicates(groups: &[DuplicateGroup], mode: LinkMode) -> Vec<LinkOutcome> {
+    let total: u64 = groups.iter().map(|g| g.files.len() as u64 - 1).sum();
+    let pb = ProgressBar::new(total);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} {msg}\n{wide_bar:.green} {pos}/{len} ({percent}%)")
+        .expect("Invalid template")
+        .progress_chars("█  "));
+    pb.set_message("Linking duplicates...");
+
+    let open_files = OpenFiles::scan_if_enabled();
+    let mut outcomes = Vec::new();
+    for group in groups {
+        let keeper = group.keeper();
+        let keeper_meta = match fs::metadata(keeper) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{}", format!("Failed to access {}: {}", keeper.display(), e).red());
+                pb.inc(group.files.len() as u64 - 1);
+                continue;
+            }
+        };
+        for path in group.redundant() {
+            pb.inc(1);
+            outcomes.push(link_one(keeper, &keeper_meta, path, mode, open_files.as_ref()));
+        }
+    }
+    pb.finish_with_message("Linking complete");
+    outcomes
+}
+
+pub fn print_link_report(outcomes: &[LinkOutcome]) {
+    let mut table = Table::new();
+    table.add_row(row!["File Path".bold().blue(), "Status".bold().blue()]);
+    for outcome in outcomes {
+        let status = if outcome.saved > 0 {
+            outcome.status.green().to_string()
+        } else {
+            outcome.status.yellow().to_string()
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&outcome.path.display().to_string().blue().to_string()),
+            Cell::new(&status),
+        ]));
+    }
+    table.printstd();
+
+    let saved: u64 = outcomes.iter().map(|o| o.saved).sum();
+    let linked = outcomes.iter().filter(|o| o.saved > 0).count();
+    println!("{}", format!("Files replaced by links: {}", linked).green());
+    println!("{}", format!("Space saved: {} bytes", saved).green());
+}
+
+// Tests Unitaires
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use tempfile::tempdir;
+
+    fn group(files: Vec<PathBuf>) -> DuplicateGroup {
+        DuplicateGroup { size: fs::metadata(&files[0]).unwrap().len(), files, keeper: 0 }
+    }
+
+    fn write_copies(dir: &Path) -> (PathBuf, PathBuf) {
+        let keeper = dir.join("a.bin");
+        let copy = dir.join("b.bin");
+        fs::write(&keeper, "shared asset").unwrap();
+        fs::write(&copy, "shared asset").unwrap();
+        let mtime = FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&keeper, mtime).unwrap();
+        filetime::set_file_mtime(&copy, mtime).unwrap();
+        (keeper, copy)
+    }
+
+    #[test]
+    fn test_hardlink_replaces_copy() {
+        let dir = tempdir().unwrap();
+        let (keeper, copy) = write_copies(dir.path());
+
+        let outcomes = link_duplicates(&[group(vec![keeper.clone(), copy.clone()])], LinkMode::Hardlink);
+
+        assert_eq!(outcomes[0].status, "Hardlinked");
+        assert_eq!(outcomes[0].saved, 12);
+        assert_eq!(fs::metadata(&keeper).unwrap().ino(), fs::metadata(&copy).unwrap().ino());
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "shared asset");
+    }
+
+    #[test]
+    fn test_hardlink_refuses_to_change_metadata() {
+        let dir = tempdir().unwrap();
+        let (keeper, copy) = write_copies(dir.path());
+        fs::set_permissions(&copy, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let outcomes = link_duplicates(&[group(vec![keeper.clone(), copy.clone()])], LinkMode::Hardlink);
+        assert_eq!(outcomes[0].status, "Permissions differ");
+
+        fs::set_permissions(&copy, fs::metadata(&keeper).unwrap().permissions()).unwrap();
+        filetime::set_file_mtime(&copy, FileTime::from_unix_time(1_700_000_000, 0)).unwrap();
+        let outcomes = link_duplicates(&[group(vec![keeper.clone(), copy.clone()])], LinkMode::Hardlink);
+        assert_eq!(outcomes[0].status, "Modification times differ");
+
+        assert_ne!(fs::metadata(&keeper).unwrap().ino(), fs::metadata(&copy).unwrap().ino());
+    }
+
+    #[test]
+    fn test_copy_edited_after_scan_is_kept() {
+        let dir = tempdir().unwrap();
+        let (keeper, copy) = write_copies(dir.path());
+        let groups = [group(vec![keeper.clone(), copy.clone()])];
+        fs::write(&copy, "edited asset").unwrap();
+
+        let outcomes = link_duplicates(&groups, LinkMode::Auto);
+        assert_eq!(outcomes[0].status, "Changed since scan");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "edited asset");
+        assert_ne!(fs::metadata(&keeper).unwrap().ino(), fs::metadata(&copy).unwrap().ino());
+    }
+
+    #[test]
+    fn test_auto_keeps_every_path_working() {
+        let dir = tempdir().unwrap();
+        let (keeper, copy) = write_copies(dir.path());
+        let mtime = FileTime::from_unix_time(1_600_000_000, 0);
+
+        let outcomes = link_duplicates(&[group(vec![keeper.clone(), copy.clone()])], LinkMode::Auto);
+
+        assert!(outcomes[0].status == "Reflinked" || outcomes[0].status == "Hardlinked");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "shared asset");
+        assert_eq!(FileTime::from_last_modification_time(&fs::metadata(&copy).unwrap()), mtime);
+        assert!(!dir.path().read_dir().unwrap().any(|e| e.unwrap().file_name().to_string_lossy().starts_with('.')));
+    }
+
+    #[test]
+    fn test_rescan_after_hardlinking_finds_nothing_to_delete() {
+        let dir = tempdir().unwrap();
+        let (keeper, copy) = write_copies(dir.path());
+        let odd = dir.path().join("c.bin");
+        fs::write(&odd, "shared asset").unwrap();
+        filetime::set_file_mtime(&odd, FileTime::from_unix_time(1_600_000_000, 0)).unwrap();
+        fs::set_permissions(&odd, fs::Permissions::from_mode(0o600)).unwrap();
+        let scan = || crate::cleaner::find_duplicate_files(&[dir.path().to_path_buf()], &Default::default(), &Default::default(), &Default::default());
+
+        let outcomes = link_duplicates(&scan(), LinkMode::Hardlink);
+        assert_eq!(outcomes.iter().filter(|o| o.status == "Hardlinked").count(), 1);
+
+        // Only the copy that could not be linked is still a duplicate.
+        let groups = scan();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reclaimable(), 12);
+        assert_eq!(groups[0].keeper(), keeper);
+        assert_eq!(groups[0].redundant().collect::<Vec<_>>(), vec![&odd]);
+        assert_eq!(fs::metadata(&keeper).unwrap().ino(), fs::metadata(&copy).unwrap().ino());
+
+        fs::set_permissions(&odd, fs::metadata(&keeper).unwrap().permissions()).unwrap();
+        link_duplicates(&groups, LinkMode::Hardlink);
+        assert!(scan().is_empty());
+    }
+}
@@ -0,0 +1,905 @@
+use crate::hash_cache::{FileKey, HashCache};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+
+/// Size of the blocks read from the start and the end of a file in the
+/// partial-hash stage.
+const PARTIAL_BLOCK_SIZE: u64 = 4096;
+
+/// Size of the buffer the full hash reads through. Files are read rather than
+/// memory-mapped: a mapped file truncated by another process while it is
+/// hashed would kill the cleaner with SIGBUS.
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Concurrent reads allowed on a device that is not known to be rotational.
+const DEFAULT_READS_PER_DEVICE: usize = 4;
+
+/// Decides which copy of a duplicate set survives.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KeepPolicy {
+    /// Keep the copy with the oldest modification time.
+    #[default]
+    Oldest,
+    /// Keep the copy with the newest modification time.
+    Newest,
+    /// Keep the copy with the shortest path.
+    ShortestPath,
+    /// Keep the oldest copy inside the given directory, if there is one.
+    PreferDir(PathBuf),
+    /// Keep the oldest copy that is not in the Downloads folder, if there is one.
+    OutsideDownloads,
+}
+
+impl KeepPolicy {
+    /// Builds a policy from its command line name; `preferred-dir` needs `prefer_dir`.
+    pub fn from_name(name: &str, prefer_dir: Option<PathBuf>) -> Result<KeepPolicy, String> {
+        match name {
+            "oldest" => Ok(KeepPolicy::Oldest),
+            "newest" => Ok(KeepPolicy::Newest),
+            "shortest-path" => Ok(KeepPolicy::ShortestPath),
+            "outside-downloads" => Ok(KeepPolicy::OutsideDownloads),
+            "preferred-dir" => prefer_dir
+                .map(KeepPolicy::PreferDir)
+                .ok_or_else(|| "the preferred-dir keep policy needs --prefer-dir".to_string()),
+            _ => Err(format!("unknown keep policy: {}", name)),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            KeepPolicy::Oldest => "oldest".to_string(),
+            KeepPolicy::Newest => "newest".to_string(),
+            KeepPolicy::ShortestPath => "shortest path".to_string(),
+            KeepPolicy::PreferDir(dir) => format!("inside {}", dir.display()),
+            KeepPolicy::OutsideDownloads => "outside Downloads".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateOptions {
+    /// Compare candidate files byte by byte after their full hashes matched.
+    pub verify_bytes: bool,
+    pub keep: KeepPolicy,
+    /// Where hashes are cached between runs; `None` hashes every file again.
+    pub hash_cache: Option<PathBuf>,
+    /// Number of hashing threads; `None` uses one per CPU.
+    pub threads: Option<usize>,
+    /// Concurrent reads per physical device; `None` allows one on spinning
+    /// disks and a few on everything else.
+    pub reads_per_device: Option<usize>,
+}
+
+/// A set of files with identical contents and the copy chosen to survive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub files: Vec<PathBuf>,
+    pub keeper: usize,
+}
+
+impl DuplicateGroup {
+    pub fn keeper(&self) -> &Path {
+        &self.files[self.keeper]
+    }
+
+    /// Every copy except the keeper. A path naming the keeper's own file,
+    /// through the same path or a hard link, is never redundant.
+    pub fn redundant(&self) -> impl Iterator<Item = &PathBuf> {
+        let keeper = self.keeper();
+        let keeper_id = file_id(keeper);
+        self.files
+            .iter()
+            .filter(move |p| p.as_path() != keeper && (keeper_id.is_none() || file_id(p) != keeper_id))
+    }
+
+    pub fn reclaimable(&self) -> u64 {
+        self.size * self.redundant().count() as u64
+    }
+}
+
+fn downloads_dir() -> Option<PathBuf> {
+    dirs::download_dir().or_else(|| dirs::home_dir().map(|h| h.join("Downloads")))
+}
+
+fn modified(path: &Path) -> std::time::SystemTime {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(std::time::UNIX_EPOCH)
+}
+
+fn oldest_of<'a>(files: impl Iterator<Item = (usize, &'a PathBuf)>) -> Option<usize> {
+    files.min_by_key(|(_, p)| modified(p)).map(|(i, _)| i)
+}
+
+/// Resolves `.`, `..` and symlinked directories in `path`, keeping its last
+/// component as is; a path that cannot be resolved is used as given.
+fn resolved(path: &Path) -> PathBuf {
+    match (path.parent().filter(|p| !p.as_os_str().is_empty()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize().map(|p| p.join(name)),
+        _ => path.canonicalize(),
+    }
+    .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns the index of the file in `files` that `policy` keeps.
+pub fn choose_keeper(files: &[PathBuf], policy: &KeepPolicy) -> usize {
+    let all = || files.iter().enumerate();
+    let inside = |dir: &Path| {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        move |path: &PathBuf| resolved(path).starts_with(&dir)
+    };
+    let chosen = match policy {
+        KeepPolicy::Oldest => oldest_of(all()),
+        KeepPolicy::Newest => all().max_by_key(|(_, p)| modified(p)).map(|(i, _)| i),
+        KeepPolicy::ShortestPath => all().min_by_key(|(_, p)| p.as_os_str().len()).map(|(i, _)| i),
+        KeepPolicy::PreferDir(dir) => {
+            let inside = inside(dir);
+            oldest_of(all().filter(|(_, p)| inside(p))).or_else(|| oldest_of(all()))
+        }
+        KeepPolicy::OutsideDownloads => match downloads_dir() {
+            Some(downloads) => {
+                let inside = inside(&downloads);
+                oldest_of(all().filter(|(_, p)| !inside(p))).or_else(|| oldest_of(all()))
+            }
+            None => oldest_of(all()),
+        },
+    };
+    chosen.unwrap_or(0)
+}
+
+fn partial_hash(path: &Path, len: u64) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; PARTIAL_BLOCK_SIZE as usize];
+
+    let head = len.min(PARTIAL_BLOCK_SIZE) as usize;
+    file.read_exact(&mut buffer[..head])?;
+    hasher.update(&buffer[..head]);
+
+    if len > PARTIAL_BLOCK_SIZE {
+        let tail = (len - PARTIAL_BLOCK_SIZE).min(PARTIAL_BLOCK_SIZE) as usize;
+        file.seek(SeekFrom::Start(len - tail as u64))?;
+        file.read_exact(&mut buffer[..tail])?;
+        hasher.update(&buffer[..tail]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn full_hash(path: &Path) -> io::Result<String> {
+    let mut file = io::BufReader::with_capacity(HASH_BUFFER_SIZE, File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(target_os = "linux")]
+fn is_rotational(dev: u64) -> bool {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let block = PathBuf::from(format!("/sys/dev/block/{}:{}", major, minor));
+    // Partitions have no queue of their own; it belongs to the parent disk.
+    [block.join("queue/rotational"), block.join("../queue/rotational")]
+        .iter()
+        .find_map(|p| std::fs::read_to_string(p).ok())
+        .is_some_and(|v| v.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational(_dev: u64) -> bool {
+    false
+}
+
+/// Device and inode of the file at `path`, shared by all its hard links.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::symlink_metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// The other hard links of each kept file, keyed by the kept path.
+type OtherLinks = HashMap<PathBuf, Vec<PathBuf>>;
+
+/// Drops repeated paths, which overlapping scan roots produce, and keeps one
+/// path per file among hard links: deleting a link frees nothing, and
+/// overwriting it would overwrite every other name of the file. Returns the
+/// kept files with the other links of each.
+fn distinct_files(mut files: Vec<(PathBuf, u64)>) -> (Vec<(PathBuf, u64)>, OtherLinks) {
+    files.sort();
+    files.dedup_by(|a, b| a.0 == b.0);
+    let mut first_of: HashMap<(u64, u64), PathBuf> = HashMap::new();
+    let mut links = OtherLinks::new();
+    let mut distinct = Vec::with_capacity(files.len());
+    for (path, len) in files {
+        match file_id(&path) {
+            Some(id) => match first_of.get(&id) {
+                Some(first) => links.entry(first.clone()).or_default().push(path),
+                None => {
+                    first_of.insert(id, path.clone());
+                    distinct.push((path, len));
+                }
+            },
+            None => distinct.push((path, len)),
+        }
+    }
+    (distinct, links)
+}
+
+#[cfg(unix)]
+fn device_of(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.dev()
+}
+
+#[cfg(not(unix))]
+fn device_of(_metadata: &std::fs::Metadata) -> u64 {
+    0
+}
+
+/// Limits how many files are read at the same time from each device so that
+/// parallel hashing does not make a spinning disk seek back and forth.
+struct DeviceThrottle {
+    reads_per_device: Option<usize>,
+    active: Mutex<HashMap<u64, usize>>,
+    limits: Mutex<HashMap<u64, usize>>,
+    released: Condvar,
+}
+
+struct ReadPermit<'a> {
+    throttle: &'a DeviceThrottle,
+    dev: u64,
+}
+
+impl DeviceThrottle {
+    fn new(reads_per_device: Option<usize>) -> Self {
+        DeviceThrottle {
+            reads_per_device,
+            active: Mutex::new(HashMap::new()),
+            limits: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    fn limit(&self, dev: u64) -> usize {
+        if let Some(limit) = self.reads_per_device {
+            return limit.max(1);
+        }
+        *self.limits.lock().unwrap().entry(dev).or_insert_with(|| {
+            if is_rotational(dev) { 1 } else { DEFAULT_READS_PER_DEVICE }
+        })
+    }
+
+    fn acquire(&self, dev: u64) -> ReadPermit<'_> {
+        let limit = self.limit(dev);
+        let mut active = self.active.lock().unwrap();
+        while *active.get(&dev).unwrap_or(&0) >= limit {
+            active = self.released.wait(active).unwrap();
+        }
+        *active.entry(dev).or_insert(0) += 1;
+        ReadPermit { throttle: self, dev }
+    }
+
+    /// Takes a permit on each of `devs`, always in the same order so that two
+    /// threads comparing files across the same devices cannot deadlock.
+    fn acquire_all(&self, devs: &[u64]) -> Vec<ReadPermit<'_>> {
+        let mut devs = devs.to_vec();
+        devs.sort_unstable();
+        devs.dedup();
+        devs.into_iter().map(|dev| self.acquire(dev)).collect()
+    }
+}
+
+impl Drop for ReadPermit<'_> {
+    fn drop(&mut self) {
+        let mut active = self.throttle.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.dev) {
+            *count -= 1;
+        }
+        self.throttle.released.notify_all();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Partial,
+    Full,
+}
+
+/// Hashes `path` for `stage`, reusing the cached hash when the file has not
+/// changed since it was last hashed.
+fn stage_hash(path: &Path, stage: Stage, cache: Option<&Mutex<HashCache>>, throttle: &DeviceThrottle) -> io::Result<String> {
+    let metadata = std::fs::metadata(path)?;
+    let key = cache.and_then(|_| FileKey::from_metadata(&metadata));
+
+    if let (Some(cache), Some(key)) = (cache, key) {
+        if let Some(entry) = cache.lock().unwrap().get(&key) {
+            let cached = match stage {
+                Stage::Partial => &entry.partial,
+                Stage::Full => &entry.full,
+            };
+            if let Some(hash) = cached {
+                return Ok(hash.clone());
+            }
+        }
+    }
+
+    let hash = {
+        let _permit = throttle.acquire(device_of(&metadata));
+        match stage {
+            Stage::Partial => partial_hash(path, metadata.len())?,
+            Stage::Full => full_hash(path)?,
+        }
+    };
+    if let (Some(cache), Some(key)) = (cache, key) {
+        let mut cache = cache.lock().unwrap();
+        match stage {
+            Stage::Partial => cache.set_partial(key, path, hash.clone()),
+            Stage::Full => cache.set_full(key, path, hash.clone()),
+        }
+    }
+    Ok(hash)
+}
+
+/// Whether the files at `a` and `b` hold the same bytes.
+pub(crate) fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let mut a = File::open(a)?;
+    let mut b = File::open(b)?;
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Splits every group in `groups` by `key`, computed in parallel, dropping
+/// files whose key could not be computed and sub-groups that end up with a
+/// single file.
+fn refine<K, F>(groups: Vec<Vec<PathBuf>>, key: F) -> Vec<Vec<PathBuf>>
+where
+    K: std::hash::Hash + Eq + Send,
+    F: Fn(&Path) -> io::Result<K> + Sync,
+{
+    let files: Vec<(usize, PathBuf)> = groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(group, files)| files.into_iter().map(move |path| (group, path)))
+        .collect();
+    let keyed: Vec<(usize, PathBuf, io::Result<K>)> = files
+        .into_par_iter()
+        .map(|(group, path)| {
+            let key = key(&path);
+            (group, path, key)
+        })
+        .collect();
+
+    let mut by_key: HashMap<(usize, K), Vec<PathBuf>> = HashMap::new();
+    for (group, path, key) in keyed {
+        match key {
+            Ok(k) => by_key.entry((group, k)).or_default().push(path),
+            Err(e) => eprintln!("Failed to read {}: {}", path.display(), e),
+        }
+    }
+    by_key.into_values().filter(|g| g.len() > 1).collect()
+}
+
+/// Partitions a group of files with equal hashes into sets whose bytes really
+/// are identical, guarding against hash collisions.
+fn confirm_bytes(group: Vec<PathBuf>, throttle: &DeviceThrottle) -> Vec<Vec<PathBuf>> {
+    // Each set keeps the device of its first file, which every other
+    // candidate is compared with.
+    let mut sets: Vec<(u64, Vec<PathBuf>)> = Vec::new();
+    for path in group {
+        let mut placed = false;
+        let dev = std::fs::metadata(&path).map(|m| device_of(&m)).unwrap_or(0);
+        for (set_dev, set) in sets.iter_mut() {
+            let _permits = throttle.acquire_all(&[*set_dev, dev]);
+            match same_contents(&set[0], &path) {
+                Ok(true) => {
+                    set.push(path.clone());
+                    placed = true;
+                    break;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    eprintln!("Failed to compare {}: {}", path.display(), e);
+                    placed = true;
+                    break;
+                }
+            }
+        }
+        if !placed {
+            sets.push((dev, vec![path]));
+        }
+    }
+    sets.into_iter().map(|(_, set)| set).filter(|s| s.len() > 1).collect()
+}
+
+/// Groups `files` (path and length) into sets of files with identical
+/// contents: by size, then by a hash of the first and last blocks, then by a
+/// full SHA-256, and optionally by a byte-by-byte comparison. Empty files are
+/// never reported, and hard links to one file count as a single file.
+pub fn group_identical(files: Vec<(PathBuf, u64)>, options: &DuplicateOptions) -> Vec<DuplicateGroup> {
+    let (files, _) = distinct_files(files);
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, len) in files {
+        if len > 0 {
+            by_size.entry(len).or_default().push(path);
+        }
+    }
+    let groups: Vec<Vec<PathBuf>> = by_size.into_values().filter(|g| g.len() > 1).collect();
+
+    let cache = options.hash_cache.as_deref().map(|path| Mutex::new(HashCache::load(path)));
+    let throttle = DeviceThrottle::new(options.reads_per_device);
+    let hash_stages = || {
+        let groups = refine(groups, |path| stage_hash(path, Stage::Partial, cache.as_ref(), &throttle));
+        let groups = refine(groups, |path| stage_hash(path, Stage::Full, cache.as_ref(), &throttle));
+        if options.verify_bytes {
+            groups.into_par_iter().flat_map_iter(|group| confirm_bytes(group, &throttle)).collect()
+        } else {
+            groups
+        }
+    };
+    let mut groups: Vec<Vec<PathBuf>> = match options.threads {
+        Some(threads) => match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+            Ok(pool) => pool.install(hash_stages),
+            Err(e) => {
+                eprintln!("Failed to start {} hashing threads: {}", threads, e);
+                hash_stages()
+            }
+        },
+        None => hash_stages(),
+    };
+    if let Some(cache) = cache {
+        if let Err(e) = cache.into_inner().unwrap().save() {
+            eprintln!("Failed to save the hash cache: {}", e);
+        }
+    }
+
+    for group in groups.iter_mut() {
+        group.sort();
+    }
+    groups.sort();
+    groups
+        .into_iter()
+        .map(|files| DuplicateGroup {
+            size: std::fs::metadata(&files[0]).map(|m| m.len()).unwrap_or(0),
+            keeper: choose_keeper(&files, &options.keep),
+            files,
+        })
+        .collect()
+}
+
+/// An entry met while walking the scanned directories, as needed to compare
+/// whole directory trees.
+#[derive(Debug, Clone)]
+pub enum TreeEntry {
+    Dir(PathBuf),
+    File(PathBuf, u64),
+    Symlink(PathBuf, PathBuf),
+    /// A directory that could not be read completely; it and its ancestors
+    /// are never reported.
+    Unreadable(PathBuf),
+}
+
+/// Directories whose recursive contents are identical.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateTreeGroup {
+    pub dirs: Vec<PathBuf>,
+    /// Total size of the files in one copy of the tree.
+    pub size: u64,
+    pub files: usize,
+    pub keeper: usize,
+}
+
+impl DuplicateTreeGroup {
+    pub fn keeper(&self) -> &Path {
+        &self.dirs[self.keeper]
+    }
+
+    pub fn redundant(&self) -> impl Iterator<Item = &PathBuf> {
+        let keeper = self.keeper;
+        self.dirs.iter().enumerate().filter(move |(i, _)| *i != keeper).map(|(_, p)| p)
+    }
+
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.dirs.len() as u64 - 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TreeSignature {
+    hash: String,
+    size: u64,
+    files: usize,
+}
+
+/// Finds directories whose contents (file names, file contents, symlink
+/// targets and sub-directories) are identical. Nested duplicates are folded
+/// into the outermost duplicate trees, and the scan roots themselves are never
+/// reported.
+pub fn group_duplicate_trees(roots: &[PathBuf], entries: Vec<TreeEntry>, options: &DuplicateOptions) -> Vec<DuplicateTreeGroup> {
+    let files: Vec<(PathBuf, u64)> = entries
+        .iter()
+        .filter_map(|e| match e {
+            TreeEntry::File(path, len) => Some((path.clone(), *len)),
+            _ => None,
+        })
+        .collect();
+
+    // Files that have no identical copy anywhere make every directory above
+    // them unique, so they get no content id.
+    // Hard links to one file share its content id.
+    let mut content_ids: HashMap<PathBuf, String> = HashMap::new();
+    let (distinct, links) = distinct_files(files.clone());
+    for (index, group) in group_identical(distinct, options).into_iter().enumerate() {
+        for path in group.files {
+            content_ids.insert(path, format!("g{}", index));
+        }
+    }
+    for (index, (first, others)) in links.into_iter().enumerate() {
+        let id = content_ids.entry(first).or_insert_with(|| format!("l{}", index)).clone();
+        for path in others {
+            content_ids.insert(path, id.clone());
+        }
+    }
+    for (path, len) in &files {
+        if *len == 0 {
+            content_ids.insert(path.clone(), "empty".to_string());
+        }
+    }
+    let sizes: HashMap<&PathBuf, u64> = files.iter().map(|(p, l)| (p, *l)).collect();
+
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let mut poisoned: std::collections::HashSet<PathBuf> = std::collections::HashSet::new();
+    let mut children: HashMap<PathBuf, Vec<&TreeEntry>> = HashMap::new();
+    for entry in &entries {
+        let path = match entry {
+            TreeEntry::Dir(path) => {
+                dirs.push(path.clone());
+                path
+            }
+            TreeEntry::File(path, _) | TreeEntry::Symlink(path, _) => path,
+            TreeEntry::Unreadable(path) => {
+                poisoned.insert(path.clone());
+                continue;
+            }
+        };
+        if let Some(parent) = path.parent() {
+            children.entry(parent.to_path_buf()).or_default().push(entry);
+        }
+    }
+
+    // Deepest directories first, so children are signed before their parents.
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+    let mut signatures: HashMap<PathBuf, Option<TreeSignature>> = HashMap::new();
+    for dir in &dirs {
+        let signature = if poisoned.contains(dir) {
+            None
+        } else {
+            let mut parts: Vec<String> = Vec::new();
+            let mut size = 0;
+            let mut file_count = 0;
+            let mut unique = false;
+            for child in children.get(dir).map(|c| c.as_slice()).unwrap_or_default() {
+                let name = |p: &Path| p.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                match child {
+                    TreeEntry::File(path, _) => match content_ids.get(path) {
+                        Some(id) => {
+                            parts.push(format!("f\0{}\0{}", name(path), id));
+                            size += sizes.get(path).copied().unwrap_or(0);
+                            file_count += 1;
+                        }
+                        None => unique = true,
+                    },
+                    TreeEntry::Symlink(path, target) => {
+                        parts.push(format!("l\0{}\0{}", name(path), target.display()));
+                    }
+                    TreeEntry::Dir(path) => match signatures.get(path).cloned().flatten() {
+                        Some(sub) => {
+                            parts.push(format!("d\0{}\0{}", name(path), sub.hash));
+                            size += sub.size;
+                            file_count += sub.files;
+                        }
+                        None => unique = true,
+                    },
+                    TreeEntry::Unreadable(_) => unique = true,
+                }
+            }
+            if unique {
+                None
+            } else {
+                parts.sort();
+                let mut hasher = Sha256::new();
+                for part in &parts {
+                    hasher.update(part.as_bytes());
+                    hasher.update(b"\n");
+                }
+                Some(TreeSignature { hash: format!("{:x}", hasher.finalize()), size, files: file_count })
+            }
+        };
+        signatures.insert(dir.clone(), signature);
+    }
+
+    let mut by_signature: HashMap<String, (TreeSignature, Vec<PathBuf>)> = HashMap::new();
+    for (dir, signature) in signatures {
+        if let Some(signature) = signature {
+            if signature.files > 0 && !roots.contains(&dir) {
+                by_signature.entry(signature.hash.clone()).or_insert_with(|| (signature, Vec::new())).1.push(dir);
+            }
+        }
+    }
+    let mut candidates: Vec<(TreeSignature, Vec<PathBuf>)> = by_signature.into_values().filter(|(_, d)| d.len() > 1).collect();
+    // Overlapping scan roots walk the same directory more than once.
+    for (_, dirs) in candidates.iter_mut() {
+        dirs.sort();
+        dirs.dedup();
+    }
+    candidates.retain(|(_, dirs)| dirs.len() > 1);
+    candidates.sort_by_key(|(_, dirs)| (dirs.iter().map(|d| d.components().count()).min(), dirs[0].clone()));
+
+    // Outer trees decide first: a tree inside a copy that will be removed goes
+    // with it, and a tree inside a kept copy must be the one kept.
+    let mut removed: Vec<PathBuf> = Vec::new();
+    let mut kept: Vec<PathBuf> = Vec::new();
+    let mut groups = Vec::new();
+    for (signature, dirs) in candidates {
+        let dirs: Vec<PathBuf> = dirs.into_iter().filter(|d| !removed.iter().any(|r| d.starts_with(r))).collect();
+        if dirs.len() < 2 {
+            continue;
+        }
+        let keeper = dirs
+            .iter()
+            .position(|d| kept.iter().any(|k| d.starts_with(k)))
+            .unwrap_or_else(|| choose_keeper(&dirs, &options.keep));
+        for (i, dir) in dirs.iter().enumerate() {
+            if i == keeper {
+                kept.push(dir.clone());
+            } else {
+                removed.push(dir.clone());
+            }
+        }
+        groups.push(DuplicateTreeGroup { dirs, size: signature.size, files: signature.files, keeper });
+    }
+    groups
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn candidates(paths: &[&PathBuf]) -> Vec<(PathBuf, u64)> {
+        paths.iter().map(|p| ((*p).clone(), fs::metadata(p).unwrap().len())).collect()
+    }
+
+    #[test]
+    fn test_same_size_different_content_is_not_duplicate() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        let c = dir.path().join("c.bin");
+        fs::write(&a, "same length 1").unwrap();
+        fs::write(&b, "same length 2").unwrap();
+        fs::write(&c, "same length 1").unwrap();
+
+        let groups = group_identical(candidates(&[&a, &b, &c]), &DuplicateOptions::default());
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, vec![a, c]);
+        assert_eq!(groups[0].reclaimable(), 13);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_are_not_duplicates() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        let c = dir.path().join("c.bin");
+        fs::write(&a, "shared inode").unwrap();
+        fs::hard_link(&a, &b).unwrap();
+        assert!(group_identical(candidates(&[&a, &b]), &DuplicateOptions::default()).is_empty());
+
+        // With a real copy, the group lists one name per file.
+        fs::write(&c, "shared inode").unwrap();
+        let groups = group_identical(candidates(&[&a, &b, &c]), &DuplicateOptions::default());
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files, vec![a.clone(), c.clone()]);
+        assert_eq!(groups[0].reclaimable(), 12);
+
+        // A group naming the keeper's inode twice never offers it.
+        let group = DuplicateGroup { size: 12, files: vec![a.clone(), b.clone()], keeper: 0 };
+        assert_eq!(group.redundant().count(), 0);
+        assert_eq!(group.reclaimable(), 0);
+    }
+
+    #[test]
+    fn test_large_files_differing_in_the_middle() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        let mut content = vec![7u8; 3 * PARTIAL_BLOCK_SIZE as usize];
+        fs::write(&a, &content).unwrap();
+        content[PARTIAL_BLOCK_SIZE as usize + 10] = 8;
+        fs::write(&b, &content).unwrap();
+
+        let options = DuplicateOptions { verify_bytes: true, ..Default::default() };
+        assert!(group_identical(candidates(&[&a, &b]), &options).is_empty());
+    }
+
+    #[test]
+    fn test_keep_policies() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("archive").join("2021");
+        fs::create_dir_all(&nested).unwrap();
+        let old = nested.join("photo.jpg");
+        let new = dir.path().join("photo.jpg");
+        fs::write(&old, "pixels").unwrap();
+        fs::write(&new, "pixels").unwrap();
+        let yesterday = std::time::SystemTime::now() - std::time::Duration::from_secs(24 * 60 * 60);
+        filetime::set_file_mtime(&old, filetime::FileTime::from_system_time(yesterday)).unwrap();
+
+        let files = vec![old.clone(), new.clone()];
+        assert_eq!(files[choose_keeper(&files, &KeepPolicy::Oldest)], old);
+        assert_eq!(files[choose_keeper(&files, &KeepPolicy::Newest)], new);
+        assert_eq!(files[choose_keeper(&files, &KeepPolicy::ShortestPath)], new);
+        assert_eq!(files[choose_keeper(&files, &KeepPolicy::PreferDir(dir.path().join("archive")))], old);
+        // A preferred directory given through a symlink still matches, even
+        // when the file outside it is older.
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&nested, dir.path().join("link")).unwrap();
+            filetime::set_file_mtime(&new, filetime::FileTime::from_unix_time(0, 0)).unwrap();
+            assert_eq!(files[choose_keeper(&files, &KeepPolicy::PreferDir(dir.path().join("link")))], old);
+            filetime::set_file_mtime(&new, filetime::FileTime::now()).unwrap();
+        }
+
+        let options = DuplicateOptions { keep: KeepPolicy::Newest, ..Default::default() };
+        let groups = group_identical(candidates(&[&old, &new]), &options);
+        assert_eq!(groups[0].keeper(), new.as_path());
+        assert_eq!(groups[0].redundant().collect::<Vec<_>>(), vec![&old]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_cache_is_reused() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        fs::write(&a, "cached content").unwrap();
+        fs::write(&b, "cached content").unwrap();
+        let cache_path = dir.path().join("hashes.tsv");
+        let options = DuplicateOptions { hash_cache: Some(cache_path.clone()), ..Default::default() };
+
+        assert_eq!(group_identical(candidates(&[&a, &b]), &options).len(), 1);
+        let cache = HashCache::load(&cache_path);
+        assert_eq!(cache.len(), 2);
+
+        // A cached full hash is trusted as long as the file key is unchanged.
+        let mut cache = HashCache::load(&cache_path);
+        let key = FileKey::from_metadata(&fs::metadata(&b).unwrap()).unwrap();
+        cache.set_full(key, &b, "stale".to_string());
+        cache.save().unwrap();
+        assert!(group_identical(candidates(&[&a, &b]), &options).is_empty());
+    }
+
+    #[test]
+    fn test_full_hash_reads_past_one_buffer() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let contents = vec![42u8; 3 * HASH_BUFFER_SIZE + 17];
+        fs::write(&a, &contents).unwrap();
+
+        assert_eq!(full_hash(&a).unwrap(), format!("{:x}", Sha256::digest(&contents)));
+    }
+
+    #[test]
+    fn test_device_throttle_limits_concurrent_reads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let throttle = DeviceThrottle::new(Some(2));
+        let current = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        (0..32).into_par_iter().for_each(|_| {
+            let _permit = throttle.acquire(7);
+            let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            current.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    fn tree_entries(root: &Path) -> Vec<TreeEntry> {
+        walkdir::WalkDir::new(root)
+            .into_iter()
+            .map(|e| e.unwrap())
+            .map(|e| {
+                if e.file_type().is_dir() {
+                    TreeEntry::Dir(e.path().to_path_buf())
+                } else {
+                    TreeEntry::File(e.path().to_path_buf(), e.metadata().unwrap().len())
+                }
+            })
+            .collect()
+    }
+
+    fn write_tree(root: &Path, extra: &str) {
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "photo a").unwrap();
+        fs::write(root.join("sub").join("b.txt"), format!("photo b{}", extra)).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_trees_are_reported_once() {
+        let dir = tempdir().unwrap();
+        let photos = dir.path().join("Photos");
+        let copy = dir.path().join("Photos (copy)");
+        let other = dir.path().join("Other");
+        write_tree(&photos, "");
+        write_tree(&copy, "");
+        write_tree(&other, " edited");
+
+        let roots = vec![dir.path().to_path_buf()];
+        let groups = group_duplicate_trees(&roots, tree_entries(dir.path()), &DuplicateOptions::default());
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].dirs, vec![photos, copy]);
+        assert_eq!(groups[0].files, 2);
+        assert_eq!(groups[0].reclaimable(), 14);
+    }
+
+    #[test]
+    fn test_nested_duplicate_keeps_copy_inside_kept_tree() {
+        let dir = tempdir().unwrap();
+        let x = dir.path().join("x");
+        let y = dir.path().join("y");
+        let z = dir.path().join("z");
+        write_tree(&x.join("a"), "");
+        write_tree(&y.join("a"), "");
+        write_tree(&z.join("a"), "");
+        fs::write(z.join("unique.txt"), "only here").unwrap();
+
+        let roots = vec![dir.path().to_path_buf()];
+        let options = DuplicateOptions { keep: KeepPolicy::ShortestPath, ..Default::default() };
+        let groups = group_duplicate_trees(&roots, tree_entries(dir.path()), &options);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].dirs, vec![x.clone(), y.clone()]);
+        assert_eq!(groups[0].keeper(), x.as_path());
+        // y/a goes away with y, and the copy inside the kept x is the keeper.
+        assert_eq!(groups[1].dirs, vec![x.join("a"), z.join("a")]);
+        assert_eq!(groups[1].keeper(), x.join("a").as_path());
+    }
+
+    #[test]
+    fn test_empty_files_are_ignored() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        assert!(group_identical(candidates(&[&a, &b]), &DuplicateOptions::default()).is_empty());
+    }
+}
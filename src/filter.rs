@@ -0,0 +1,533 @@
+use crate::age::{parse_duration, TimeField};
+use crate::cleaner::{has_permission, is_permission_denied};
+use crate::mounts;
+use crate::safe_delete;
+use crate::size::{self, parse_size};
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case, take_while, take_while1};
+use nom::character::complete::{char, multispace0, satisfy};
+use nom::combinator::{not, peek};
+use nom::multi::separated_list1;
+use nom::sequence::{delimited, preceded, terminated};
+use nom::IResult;
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextField {
+    Ext,
+    Name,
+    Path,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn from_symbol(symbol: &str) -> Option<Cmp> {
+        match symbol {
+            "=" => Some(Cmp::Eq),
+            "!=" => Some(Cmp::Ne),
+            "<" => Some(Cmp::Lt),
+            "<=" => Some(Cmp::Le),
+            ">" => Some(Cmp::Gt),
+            ">=" => Some(Cmp::Ge),
+            _ => None,
+        }
+    }
+
+    fn holds<T: Ord>(&self, left: T, right: T) -> bool {
+        match self {
+            Cmp::Eq => left == right,
+            Cmp::Ne => left != right,
+            Cmp::Lt => left < right,
+            Cmp::Le => left <= right,
+            Cmp::Gt => left > right,
+            Cmp::Ge => left >= right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    Text { field: TextField, negate: bool, value: String },
+    In { field: TextField, values: Vec<String> },
+    Glob { field: TextField, pattern: Regex },
+    Size { cmp: Cmp, bytes: u64 },
+    Age { field: TimeField, cmp: Cmp, age: Duration },
+    Type { negate: bool, kind: Kind },
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+}
+
+/// Converts a glob to an anchored regex: `**/` matches any number of
+/// directories, `*` and `?` never match `/`.
+fn glob_to_regex(glob: &str) -> Regex {
+    let mut pattern = String::from("^");
+    let mut rest = glob;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("**/") {
+            pattern.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            pattern.push_str(".*");
+            rest = after;
+        } else {
+            match c {
+                '*' => pattern.push_str("[^/]*"),
+                '?' => pattern.push_str("[^/]"),
+                c => pattern.push_str(&regex::escape(&c.to_string())),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).unwrap()
+}
+
+fn text_of(field: TextField, path: &Path) -> String {
+    match field {
+        TextField::Ext => path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default(),
+        TextField::Name => path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        TextField::Path => path.to_string_lossy().into_owned(),
+    }
+}
+
+impl Test {
+    fn matches(&self, path: &Path, metadata: &fs::Metadata, now: DateTime<Utc>) -> bool {
+        match self {
+            Test::Text { field, negate, value } => (text_of(*field, path) == *value) != *negate,
+            Test::In { field, values } => values.contains(&text_of(*field, path)),
+            Test::Glob { field, pattern } => pattern.is_match(&text_of(*field, path)),
+            Test::Size { cmp, bytes } => cmp.holds(metadata.len(), *bytes),
+            Test::Age { field, cmp, age } => field.of(metadata).is_some_and(|time| cmp.holds(now.signed_duration_since(time), *age)),
+            Test::Type { negate, kind } => {
+                let file_type = metadata.file_type();
+                let is_kind = match kind {
+                    Kind::File => file_type.is_file(),
+                    Kind::Dir => file_type.is_dir(),
+                    Kind::Symlink => file_type.is_symlink(),
+                };
+                is_kind != *negate
+            }
+        }
+    }
+}
+
+impl Expr {
+    fn matches(&self, path: &Path, metadata: &fs::Metadata, now: DateTime<Utc>) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(path, metadata, now) && right.matches(path, metadata, now),
+            Expr::Or(left, right) => left.matches(path, metadata, now) || right.matches(path, metadata, now),
+            Expr::Not(inner) => !inner.matches(path, metadata, now),
+            Expr::Test(test) => test.matches(path, metadata, now),
+        }
+    }
+}
+
+/// Parse error: where parsing stopped and what was expected there.
+#[derive(Debug)]
+struct Error<'a> {
+    input: &'a str,
+    message: Option<String>,
+}
+
+impl<'a> nom::error::ParseError<&'a str> for Error<'a> {
+    fn from_error_kind(input: &'a str, _kind: nom::error::ErrorKind) -> Self {
+        Error { input, message: None }
+    }
+
+    fn append(_input: &'a str, _kind: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keeps the alternative that got furthest into the input.
+    fn or(self, other: Self) -> Self {
+        if other.input.len() <= self.input.len() { other } else { self }
+    }
+}
+
+type Res<'a, T> = IResult<&'a str, T, Error<'a>>;
+
+fn fail<'a, T>(at: &'a str, message: String) -> Res<'a, T> {
+    Err(nom::Err::Failure(Error { input: at, message: Some(message) }))
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "._-/*?+:".contains(c)
+}
+
+fn word(input: &str) -> Res<'_, &str> {
+    take_while1(is_word_char)(input)
+}
+
+fn keyword<'a>(name: &'static str) -> impl FnMut(&'a str) -> Res<'a, &'a str> {
+    preceded(multispace0, terminated(tag_no_case(name), not(peek(satisfy(is_word_char)))))
+}
+
+/// A bare word or a string in double or single quotes.
+fn string(input: &str) -> Res<'_, String> {
+    let (input, _) = multispace0(input)?;
+    let (rest, value) = alt((
+        delimited(char('"'), take_while(|c| c != '"'), char('"')),
+        delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+        word,
+    ))(input)?;
+    Ok((rest, value.to_string()))
+}
+
+fn operator(input: &str) -> Res<'_, &str> {
+    let (input, _) = multispace0(input)?;
+    alt((tag("!="), tag("<="), tag(">="), tag("="), tag("<"), tag(">"), tag("~"), keyword("in")))(input)
+}
+
+struct Parser<'r> {
+    rules: &'r BTreeMap<String, String>,
+}
+
+impl Parser<'_> {
+    fn or_expr<'a>(&self, input: &'a str) -> Res<'a, Expr> {
+        let (mut input, mut expr) = self.and_expr(input)?;
+        while let Ok((rest, _)) = keyword("or")(input) {
+            let (rest, right) = self.and_expr(rest).map_err(cut)?;
+            expr = Expr::Or(Box::new(expr), Box::new(right));
+            input = rest;
+        }
+        Ok((input, expr))
+    }
+
+    fn and_expr<'a>(&self, input: &'a str) -> Res<'a, Expr> {
+        let (mut input, mut expr) = self.unary(input)?;
+        while let Ok((rest, _)) = keyword("and")(input) {
+            let (rest, right) = self.unary(rest).map_err(cut)?;
+            expr = Expr::And(Box::new(expr), Box::new(right));
+            input = rest;
+        }
+        Ok((input, expr))
+    }
+
+    fn unary<'a>(&self, input: &'a str) -> Res<'a, Expr> {
+        if let Ok((rest, _)) = keyword("not")(input) {
+            let (rest, inner) = self.unary(rest).map_err(cut)?;
+            return Ok((rest, Expr::Not(Box::new(inner))));
+        }
+        self.primary(input)
+    }
+
+    fn primary<'a>(&self, input: &'a str) -> Res<'a, Expr> {
+        let (input, _) = multispace0(input)?;
+        if let Some(rest) = input.strip_prefix('(') {
+            let (rest, expr) = self.or_expr(rest).map_err(cut)?;
+            let (rest, _) = multispace0(rest)?;
+            return match rest.strip_prefix(')') {
+                Some(rest) => Ok((rest, expr)),
+                None => fail(rest, "expected ')'".to_string()),
+            };
+        }
+        if let Some(rest) = input.strip_prefix('@') {
+            let (rest, name) = word(rest).map_err(|_| nom::Err::Failure(Error { input: rest, message: Some("expected a rule name".to_string()) }))?;
+            let Some(text) = self.rules.get(name) else {
+                return fail(input, format!("unknown rule '@{}'", name));
+            };
+            // Rules cannot refer to other rules, which rules out cycles.
+            let expr = parse_expr(text, &BTreeMap::new())
+                .map_err(|e| nom::Err::Failure(Error { input, message: Some(format!("rule '@{}' is invalid: {}", name, e)) }))?;
+            return Ok((rest, expr));
+        }
+        self.comparison(input)
+    }
+
+    fn comparison<'a>(&self, input: &'a str) -> Res<'a, Expr> {
+        const FIELDS: &str = "ext, name, path, size, age, mtime, atime, ctime, btime or type";
+        let Ok((rest, field)) = word(input) else {
+            return Err(nom::Err::Error(Error { input, message: Some(format!("expected a field ({}), 'not' or '('", FIELDS)) }));
+        };
+        let field = field.to_ascii_lowercase();
+        let (rest, _) = multispace0(rest)?;
+        let op_at = rest;
+        let Ok((rest, op)) = operator(rest) else {
+            return fail(op_at, "expected an operator (=, !=, <, <=, >, >=, ~ or in)".to_string());
+        };
+        let op = op.to_ascii_lowercase();
+        let (rest, _) = multispace0(rest)?;
+        let value_at = rest;
+        let cmp = Cmp::from_symbol(&op);
+        match field.as_str() {
+            "ext" | "name" | "path" => {
+                let text_field = match field.as_str() {
+                    "ext" => TextField::Ext,
+                    "name" => TextField::Name,
+                    _ => TextField::Path,
+                };
+                let normalize = |value: String| match text_field {
+                    TextField::Ext => value.trim_start_matches('.').to_lowercase(),
+                    _ => value,
+                };
+                match op.as_str() {
+                    "in" => {
+                        let list = delimited(
+                            preceded(multispace0, char('(')),
+                            separated_list1(preceded(multispace0, char(',')), string),
+                            preceded(multispace0, char(')')),
+                        )(rest);
+                        let Ok((rest, values)) = list else {
+                            return fail(value_at, "expected a list of values such as (log, tmp)".to_string());
+                        };
+                        Ok((rest, Expr::Test(Test::In { field: text_field, values: values.into_iter().map(normalize).collect() })))
+                    }
+                    "~" => {
+                        let Ok((rest, glob)) = string(rest) else { return fail(value_at, "expected a glob pattern".to_string()) };
+                        Ok((rest, Expr::Test(Test::Glob { field: text_field, pattern: glob_to_regex(&normalize(glob)) })))
+                    }
+                    "=" | "!=" => {
+                        let Ok((rest, value)) = string(rest) else { return fail(value_at, "expected a value".to_string()) };
+                        Ok((rest, Expr::Test(Test::Text { field: text_field, negate: op == "!=", value: normalize(value) })))
+                    }
+                    _ => fail(op_at, format!("operator '{}' does not apply to {}; use =, !=, ~ or in", op, field)),
+                }
+            }
+            "size" | "age" | "mtime" | "atime" | "ctime" | "btime" => {
+                let Some(cmp) = cmp else {
+                    return fail(op_at, format!("operator '{}' does not apply to {}; use =, !=, <, <=, > or >=", op, field));
+                };
+                let Ok((rest, value)) = word(rest) else { return fail(value_at, format!("expected a {}", if field == "size" { "size such as 100M" } else { "duration such as 30d" })) };
+                let test = if field == "size" {
+                    match parse_size(value) {
+                        Ok(bytes) => Test::Size { cmp, bytes },
+                        Err(e) => return fail(value_at, e),
+                    }
+                } else {
+                    let time_field = if field == "age" { TimeField::Modified } else { TimeField::from_name(&field).unwrap() };
+                    match parse_duration(value) {
+                        Ok(age) => Test::Age { field: time_field, cmp, age },
+                        Err(e) => return fail(value_at, e),
+                    }
+                };
+                Ok((rest, Expr::Test(test)))
+            }
+            "type" => {
+                if op != "=" && op != "!=" {
+                    return fail(op_at, format!("operator '{}' does not apply to type; use = or !=", op));
+                }
+                let (rest, value) = word(rest).unwrap_or((rest, ""));
+                let kind = match value {
+                    "file" | "f" => Kind::File,
+                    "dir" | "d" => Kind::Dir,
+                    "symlink" | "link" | "l" => Kind::Symlink,
+                    _ => return fail(value_at, "expected file, dir or symlink".to_string()),
+                };
+                Ok((rest, Expr::Test(Test::Type { negate: op == "!=", kind })))
+            }
+            _ => fail(input, format!("unknown field '{}', expected {}", field, FIELDS)),
+        }
+    }
+}
+
+/// Turns a recoverable error into one that stops the parse: once a keyword
+/// has been read, what follows must parse.
+fn cut(err: nom::Err<Error<'_>>) -> nom::Err<Error<'_>> {
+    match err {
+        nom::Err::Error(e) => nom::Err::Failure(e),
+        other => other,
+    }
+}
+
+/// Builds the message of a parse error, with the expression and a caret under
+/// the offending token.
+fn describe(text: &str, at: &str, message: &str) -> String {
+    let offset = text.len() - at.len();
+    let column = text[..offset].chars().count();
+    let found = match at.split_whitespace().next() {
+        Some(token) => format!("'{}'", token),
+        None => "the end of the expression".to_string(),
+    };
+    format!("{} at column {}, found {}\n    {}\n    {}^", message, column + 1, found, text, " ".repeat(column))
+}
+
+fn parse_expr(text: &str, rules: &BTreeMap<String, String>) -> Result<Expr, String> {
+    let parser = Parser { rules };
+    match parser.or_expr(text) {
+        Ok((rest, expr)) => {
+            let (rest, _) = multispace0::<_, Error>(rest).unwrap();
+            if rest.is_empty() {
+                Ok(expr)
+            } else {
+                Err(describe(text, rest, "expected 'and', 'or' or the end of the expression"))
+            }
+        }
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(describe(text, e.input, e.message.as_deref().unwrap_or("invalid expression")))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(describe(text, "", "incomplete expression")),
+    }
+}
+
+/// A `--where` expression such as
+/// `ext in (log,tmp) and size > 100M and age > 30d and not path ~ "**/keep/**"`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses `text`; `@name` refers to the expression of the config rule
+    /// `name`. Errors show the expression with a caret under the offending
+    /// token.
+    pub fn parse(text: &str, rules: &BTreeMap<String, String>) -> Result<Filter, String> {
+        Ok(Filter { expr: parse_expr(text, rules)? })
+    }
+
+    pub fn matches(&self, path: &Path, metadata: &fs::Metadata, now: DateTime<Utc>) -> bool {
+        self.expr.matches(path, metadata, now)
+    }
+}
+
+fn active_filter() -> &'static RwLock<Option<Filter>> {
+    static FILTER: RwLock<Option<Filter>> = RwLock::new(None);
+    &FILTER
+}
+
+/// Replaces the expression every file scan is restricted to.
+pub fn set_filter(filter: Option<Filter>) {
+    *active_filter().write().unwrap() = filter;
+}
+
+/// Whether a file passes the `--where` expression, if one was given.
+pub fn accepts(path: &Path, metadata: &fs::Metadata) -> bool {
+    active_filter().read().unwrap().as_ref().is_none_or(|filter| filter.matches(path, metadata, Utc::now()))
+}
+
+/// Finds the files under `dirs_to_scan` matched by `filter`.
+pub fn find_matching_files<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>, filter: &Filter) -> Vec<PathBuf> {
+    let now = Utc::now();
+    let mut files = Vec::new();
+
+    for dir in dirs_to_scan {
+        let dir = dir.as_ref();
+        if exclude_dirs.contains(dir.to_str().unwrap_or_default()) || !has_permission(dir) {
+            continue;
+        }
+        let walker = mounts::walk_filtered(WalkDir::new(dir).min_depth(1), |e| {
+            !exclude_dirs.contains(e.path().to_str().unwrap_or_default())
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    // Directories, FIFOs, sockets and device nodes are not
+                    // files to clean, whatever the expression says.
+                    let file_type = entry.file_type();
+                    if !file_type.is_file() && !file_type.is_symlink() {
+                        continue;
+                    }
+                    if let Ok(metadata) = entry.metadata() {
+                        if size::accepts(&metadata) && filter.matches(entry.path(), &metadata, now) {
+                            safe_delete::record_walked(&entry, &metadata);
+                            files.push(entry.path().to_path_buf());
+                        }
+                    }
+                }
+                Err(e) => {
+                    if is_permission_denied(&e) {
+                        eprintln!("{}", format!("Permission denied: {}", e).red());
+                    } else {
+                        eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                    }
+                }
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_filter_matches_files() {
+        let dir = tempdir().unwrap();
+        let keep = dir.path().join("keep");
+        fs::create_dir_all(&keep).unwrap();
+        let big = dir.path().join("big.LOG");
+        let small = dir.path().join("small.tmp");
+        let kept = keep.join("kept.log");
+        fs::write(&big, vec![0u8; 2048]).unwrap();
+        fs::write(&small, vec![0u8; 10]).unwrap();
+        fs::write(&kept, vec![0u8; 4096]).unwrap();
+        fs::write(dir.path().join("notes.txt"), vec![0u8; 4096]).unwrap();
+
+        let filter = Filter::parse(r#"ext in (log, .TMP) and (size > 1K or name = small.tmp) and not path ~ "**/keep/**""#, &BTreeMap::new()).unwrap();
+        assert_eq!(find_matching_files(&[dir.path()], &HashSet::new(), &filter), vec![big.clone(), small]);
+
+        let rules = BTreeMap::from([("old-logs".to_string(), "ext = log and age > 30d".to_string())]);
+        let filter = Filter::parse("@old-logs or name ~ '*.txt' and size >= 4K", &rules).unwrap();
+        assert_eq!(find_matching_files(&[dir.path()], &HashSet::new(), &filter), vec![dir.path().join("notes.txt")]);
+        let filter = Filter::parse("not @old-logs and type = file and mtime < 1h", &rules).unwrap();
+        assert_eq!(find_matching_files(&[dir.path()], &HashSet::new(), &filter).len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_filter_skips_fifos() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("app.log");
+        let fifo = dir.path().join("pipe.log");
+        fs::write(&log, "log").unwrap();
+        let fifo_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo_path.as_ptr(), 0o600) }, 0);
+
+        let filter = Filter::parse("ext = log", &BTreeMap::new()).unwrap();
+        assert_eq!(find_matching_files(&[dir.path()], &HashSet::new(), &filter), vec![log]);
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_the_token() {
+        let rules = BTreeMap::new();
+        let err = Filter::parse("ext in (log,tmp) and size > 100Q", &rules).unwrap_err();
+        assert!(err.starts_with("Unknown size unit 'Q'"), "{}", err);
+        assert!(err.contains("at column 29, found '100Q'"), "{}", err);
+        assert!(err.ends_with(&format!("\n    {}^", " ".repeat(28))), "{}", err);
+
+        let err = Filter::parse("colour = red", &rules).unwrap_err();
+        assert!(err.starts_with("unknown field 'colour'") && err.contains("column 1"), "{}", err);
+        let err = Filter::parse("size ~ 10M", &rules).unwrap_err();
+        assert!(err.starts_with("operator '~' does not apply to size") && err.contains("column 6"), "{}", err);
+        let err = Filter::parse("age > 30d and", &rules).unwrap_err();
+        assert!(err.contains("found the end of the expression"), "{}", err);
+        let err = Filter::parse("(size > 1M or ext = log", &rules).unwrap_err();
+        assert!(err.starts_with("expected ')'"), "{}", err);
+        let err = Filter::parse("size > 1M ext = log", &rules).unwrap_err();
+        assert!(err.starts_with("expected 'and', 'or'") && err.contains("found 'ext'"), "{}", err);
+        assert!(Filter::parse("@missing", &rules).unwrap_err().starts_with("unknown rule '@missing'"));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# rustcleaner hash cache v1";
+
+/// Identifies one version of a file's contents: a file that is rewritten gets
+/// a new size or modification time and therefore a new key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileKey {
+    pub dev: u64,
+    pub ino: u64,
+    pub size: u64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+}
+
+impl FileKey {
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &fs::Metadata) -> Option<FileKey> {
+        use std::os::unix::fs::MetadataExt;
+        Some(FileKey {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            size: metadata.size(),
+            mtime_sec: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+        })
+    }
+
+    /// Without device and inode numbers a file cannot be identified reliably,
+    /// so nothing is cached.
+    #[cfg(not(unix))]
+    pub fn from_metadata(_metadata: &fs::Metadata) -> Option<FileKey> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub partial: Option<String>,
+    pub full: Option<String>,
+}
+
+/// Content hashes from earlier duplicate scans, stored as a tab separated
+/// file so that unchanged files are not read again.
+#[derive(Debug, Default)]
+pub struct HashCache {
+    path: PathBuf,
+    entries: HashMap<FileKey, CacheEntry>,
+    dirty: bool,
+}
+
+pub fn default_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rustcleaner")
+        .join("hashes.tsv")
+}
+
+fn parse_hash(field: &str) -> Option<String> {
+    if field == "-" { None } else { Some(field.to_string()) }
+}
+
+fn parse_line(line: &str) -> Option<(FileKey, CacheEntry)> {
+    let mut fields = line.splitn(8, '\t');
+    let key = FileKey {
+        dev: fields.next()?.parse().ok()?,
+        ino: fields.next()?.parse().ok()?,
+        size: fields.next()?.parse().ok()?,
+        mtime_sec: fields.next()?.parse().ok()?,
+        mtime_nsec: fields.next()?.parse().ok()?,
+    };
+    let partial = parse_hash(fields.next()?);
+    let full = parse_hash(fields.next()?);
+    let path = PathBuf::from(fields.next()?);
+    Some((key, CacheEntry { path, partial, full }))
+}
+
+impl HashCache {
+    /// Loads the cache at `path`; a missing or unreadable cache is empty.
+    pub fn load(path: &Path) -> HashCache {
+        let mut cache = HashCache { path: path.to_path_buf(), ..Default::default() };
+        if let Ok(file) = fs::File::open(path) {
+            for line in BufReader::new(file).lines().map_while(Result::ok) {
+                if line.starts_with('#') {
+                    continue;
+                }
+                if let Some((key, entry)) = parse_line(&line) {
+                    cache.entries.insert(key, entry);
+                }
+            }
+        }
+        cache
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &FileKey) -> Option<&CacheEntry> {
+        self.entries.get(key)
+    }
+
+    pub fn set_partial(&mut self, key: FileKey, path: &Path, hash: String) {
+        let entry = self.entries.entry(key).or_default();
+        entry.path = path.to_path_buf();
+        entry.partial = Some(hash);
+        self.dirty = true;
+    }
+
+    pub fn set_full(&mut self, key: FileKey, path: &Path, hash: String) {
+        let entry = self.entries.entry(key).or_default();
+        entry.path = path.to_path_buf();
+        entry.full = Some(hash);
+        self.dirty = true;
+    }
+
+    /// Drops entries whose file was deleted or changed since it was hashed,
+    /// and returns how many were removed.
+    pub fn prune(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|key, entry| {
+            fs::symlink_metadata(&entry.path)
+                .ok()
+                .and_then(|m| FileKey::from_metadata(&m))
+                .is_some_and(|current| current == *key)
+        });
+        let removed = before - self.entries.len();
+        if removed > 0 {
+            self.dirty = true;
+        }
+        removed
+    }
+
+    /// Writes the cache back if it changed, replacing the old file atomically.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp = self.path.with_extension("tsv.tmp");
+        let mut out = io::BufWriter::new(fs::File::create(&temp)?);
+        writeln!(out, "{}", HEADER)?;
+        for (key, entry) in &self.entries {
+            let path = entry.path.to_string_lossy();
+            if path.contains('\n') {
+                continue;
+            }
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                key.dev,
+                key.ino,
+                key.size,
+                key.mtime_sec,
+                key.mtime_nsec,
+                entry.partial.as_deref().unwrap_or("-"),
+                entry.full.as_deref().unwrap_or("-"),
+                path
+            )?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+// Tests Unitaires
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_cache_round_trip_and_prune() {
+        let dir = tempdir().unwrap();
+        let cache_path = dir.path().join("cache").join("hashes.tsv");
+        let kept = dir.path().join("kept.txt");
+        let changed = dir.path().join("changed.txt");
+        fs::write(&kept, "a").unwrap();
+        fs::write(&changed, "b").unwrap();
+        let kept_key = FileKey::from_metadata(&fs::metadata(&kept).unwrap()).unwrap();
+        let changed_key = FileKey::from_metadata(&fs::metadata(&changed).unwrap()).unwrap();
+
+        let mut cache = HashCache::load(&cache_path);
+        cache.set_partial(kept_key, &kept, "p1".to_string());
+        cache.set_full(kept_key, &kept, "f1".to_string());
+        cache.set_full(changed_key, &changed, "f2".to_string());
+        cache.save().unwrap();
+
+        let mut cache = HashCache::load(&cache_path);
+        assert_eq!(cache.len(), 2);
+        let entry = cache.get(&kept_key).unwrap();
+        assert_eq!(entry.partial.as_deref(), Some("p1"));
+        assert_eq!(entry.full.as_deref(), Some("f1"));
+
+        fs::write(&changed, "bigger").unwrap();
+        assert_eq!(cache.prune(), 1);
+        assert!(cache.get(&changed_key).is_none());
+        assert!(cache.get(&kept_key).is_some());
+    }
+}
@@ -1,6 +1,6 @@
 use dialoguer::Input;
-use clap::{Arg, Command};
-use dialoguer::{Select, Confirm};
+use clap::{Arg, ArgMatches, Command};
+use dialoguer::{Select, Confirm, MultiSelect};
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::time::{SystemTime, Duration};
@@ -8,11 +8,32 @@ use tokio::task;
 use tokio::sync::mpsc;
 use std::str::FromStr;
 use cron::Schedule;
-use dirs;
 use chrono::Utc;
 use terminal_size::{Width, terminal_size};
 
+mod age;
+mod browsers;
+mod certificate;
 mod cleaner;
+mod config;
+mod dedupe;
+mod duplicates;
+mod filter;
+mod hash_cache;
+mod mounts;
+mod open_files;
+mod package_caches;
+mod picker;
+mod projects;
+mod protect;
+mod rust_cache;
+mod safe_delete;
+mod similar_images;
+mod size;
+mod symlinks;
+mod system_packages;
+mod tui;
+mod usage;
 
 /// Obtient les répertoires exclus par défaut en fonction du système d'exploitation.
 fn get_default_excludes() -> HashSet<String> {
@@ -47,12 +68,45 @@ fn get_default_excludes() -> HashSet<String> {
     }
 }
 
+/// Construit les options de détection des doublons à partir des arguments de la ligne de commande.
+fn duplicate_options(matches: &ArgMatches) -> Result<duplicates::DuplicateOptions, String> {
+    let keep = duplicates::KeepPolicy::from_name(
+        matches.get_one::<String>("keep").unwrap(),
+        matches.get_one::<String>("prefer-dir").map(PathBuf::from),
+    )?;
+    Ok(duplicates::DuplicateOptions {
+        verify_bytes: matches.get_flag("verify-bytes"),
+        keep,
+        hash_cache: if matches.get_flag("no-hash-cache") { None } else { Some(hash_cache::default_path()) },
+        threads: matches.get_one::<usize>("hash-threads").copied(),
+        reads_per_device: matches.get_one::<usize>("reads-per-device").copied(),
+    })
+}
+
+/// Construit la règle d'âge de l'analyse de l'espace disque, si --older-than ou --newer-than est passé.
+fn age_rule(matches: &ArgMatches) -> Option<age::AgeRule> {
+    let older_than = matches.get_one::<chrono::Duration>("older-than").copied();
+    let newer_than = matches.get_one::<chrono::Duration>("newer-than").copied();
+    (older_than.is_some() || newer_than.is_some()).then(|| age::AgeRule {
+        field: age::TimeField::from_name(matches.get_one::<String>("time-field").unwrap()).unwrap(),
+        older_than,
+        newer_than,
+    })
+}
+
+/// Supprime les répertoires vidés par un nettoyage si --remove-empty-dirs est passé.
+fn remove_empty_dirs_after_clean<P: AsRef<std::path::Path>>(matches: &ArgMatches, deleted: &[PathBuf], roots: &[P], exclude_dirs: &HashSet<String>) {
+    if matches.get_flag("remove-empty-dirs") {
+        cleaner::remove_emptied_dirs(deleted, roots, exclude_dirs);
+    }
+}
+
 /// Exécute une tâche de nettoyage planifiée en fonction de l'expression cron fournie.
-async fn run_scheduled_job(schedule_expression: &str, dirs_to_scan: Vec<PathBuf>, exclude_dirs: HashSet<String>, exclude_types: HashSet<String>, secure_clean: bool, mut shutdown: mpsc::Receiver<()>) {
+async fn run_scheduled_job(schedule_expression: &str, dirs_to_scan: Vec<PathBuf>, exclude_dirs: HashSet<String>, exclude_types: HashSet<String>, secure_clean: bool, remove_empty: bool, mut shutdown: mpsc::Receiver<()>) {
     let schedule = Schedule::from_str(schedule_expression).unwrap();
-    let mut upcoming = schedule.upcoming(Utc);
+    let upcoming = schedule.upcoming(Utc);
 
-    while let Some(next) = upcoming.next() {
+    for next in upcoming {
         let now = Utc::now();
         let duration = next - now;
         let delay = duration.to_std().unwrap_or(Duration::from_secs(60));
@@ -60,10 +114,14 @@ async fn run_scheduled_job(schedule_expression: &str, dirs_to_scan: Vec<PathBuf>
         tokio::select! {
             _ = tokio::time::sleep(delay) => {
                 println!("Running scheduled cleaning job...");
+                safe_delete::clear_record();
                 let files_to_clean = cleaner::scan_files(&dirs_to_scan, &exclude_dirs, &exclude_types);
 
                 if !files_to_clean.is_empty() {
-                    cleaner::clean_files(files_to_clean, secure_clean);
+                    cleaner::clean_files(files_to_clean.clone(), secure_clean);
+                    if remove_empty {
+                        cleaner::remove_emptied_dirs(&files_to_clean, &dirs_to_scan, &exclude_dirs);
+                    }
                 } else {
                     println!("No files to clean.");
                 }
@@ -100,6 +158,9 @@ async fn main() {
     println!();
 
     loop {
+        // Chaque action ne supprime que ce que sa propre analyse a trouvé.
+        safe_delete::clear_record();
+
         // Définition des options en fonction de la langue
         let options = match lang {
             "en" => &[
@@ -114,7 +175,20 @@ async fn main() {
                 "9 => Clean browser cache files",
                 "10 => Restore deleted files",
                 "11 => Secure file cleaning",
-                "12 => Clean files older than a specified number of days",
+                "12 => Clean files older than a specified age",
+                "13 => Prune the hash cache used for duplicate detection",
+                "14 => Find duplicate directory trees",
+                "15 => Find similar images",
+                "16 => Remove empty directories",
+                "17 => Find broken symbolic links",
+                "18 => Find the largest files and directories",
+                "19 => Explore disk usage interactively",
+                "20 => Find empty files",
+                "21 => Clean files matching a filter expression",
+                "22 => Clean build artifacts of inactive projects",
+                "23 => Clean the Cargo and rustup caches",
+                "24 => Clean package manager caches",
+                "25 => Remove old versions from the apt, dnf and pacman caches",
                 "q => Enter q to quit"
             ],
             "fr" => &[
@@ -129,7 +203,20 @@ async fn main() {
                 "9 => Nettoyer les fichiers de cache du navigateur",
                 "10 => Restaurer les fichiers supprimés",
                 "11 => Nettoyage sécurisé des fichiers",
-                "12 => Nettoyer les fichiers plus anciens qu'un nombre de jours spécifié",
+                "12 => Nettoyer les fichiers plus anciens qu'un âge spécifié",
+                "13 => Purger le cache de hachage utilisé pour la détection des doublons",
+                "14 => Rechercher les arborescences de répertoires en double",
+                "15 => Rechercher les images similaires",
+                "16 => Supprimer les répertoires vides",
+                "17 => Rechercher les liens symboliques cassés",
+                "18 => Rechercher les fichiers et répertoires les plus volumineux",
+                "19 => Explorer l'espace disque de façon interactive",
+                "20 => Rechercher les fichiers vides",
+                "21 => Nettoyer les fichiers correspondant à une expression de filtre",
+                "22 => Nettoyer les artefacts de compilation des projets inactifs",
+                "23 => Nettoyer les caches de Cargo et rustup",
+                "24 => Nettoyer les caches des gestionnaires de paquets",
+                "25 => Supprimer les anciennes versions des caches apt, dnf et pacman",
                 "q => Entrer q pour quitter"
             ],
             _ => &[
@@ -144,7 +231,20 @@ async fn main() {
                 "9 => Clean browser cache files",
                 "10 => Restore deleted files",
                 "11 => Secure file cleaning",
-                "12 => Clean files older than a specified number of days",
+                "12 => Clean files older than a specified age",
+                "13 => Prune the hash cache used for duplicate detection",
+                "14 => Find duplicate directory trees",
+                "15 => Find similar images",
+                "16 => Remove empty directories",
+                "17 => Find broken symbolic links",
+                "18 => Find the largest files and directories",
+                "19 => Explore disk usage interactively",
+                "20 => Find empty files",
+                "21 => Clean files matching a filter expression",
+                "22 => Clean build artifacts of inactive projects",
+                "23 => Clean the Cargo and rustup caches",
+                "24 => Clean package manager caches",
+                "25 => Remove old versions from the apt, dnf and pacman caches",
                 "q => Enter q to quit"
             ]
         };
@@ -266,6 +366,232 @@ async fn main() {
                     _ => "Analyzes and removes duplicate files",
                 })
                 .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("verify-bytes")
+                .long("verify-bytes")
+                .help(match lang {
+                    "en" => "Confirms duplicate files byte by byte after hashing",
+                    "fr" => "Confirme les fichiers en double octet par octet après le hachage",
+                    _ => "Confirms duplicate files byte by byte after hashing",
+                })
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("no-hash-cache")
+                .long("no-hash-cache")
+                .help(match lang {
+                    "en" => "Hashes every file again instead of reusing cached hashes",
+                    "fr" => "Recalcule le hachage de chaque fichier au lieu de réutiliser le cache",
+                    _ => "Hashes every file again instead of reusing cached hashes",
+                })
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("hash-threads")
+                .long("hash-threads")
+                .value_name("THREADS")
+                .value_parser(clap::value_parser!(usize))
+                .help(match lang {
+                    "en" => "Number of threads used to hash files when looking for duplicates",
+                    "fr" => "Nombre de threads utilisés pour hacher les fichiers lors de la recherche de doublons",
+                    _ => "Number of threads used to hash files when looking for duplicates",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("reads-per-device")
+                .long("reads-per-device")
+                .value_name("READS")
+                .value_parser(clap::value_parser!(usize))
+                .help(match lang {
+                    "en" => "Maximum number of files read at once from each disk (default: 1 on spinning disks)",
+                    "fr" => "Nombre maximal de fichiers lus simultanément sur chaque disque (par défaut : 1 sur les disques durs)",
+                    _ => "Maximum number of files read at once from each disk (default: 1 on spinning disks)",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("keep")
+                .long("keep")
+                .value_name("POLICY")
+                .value_parser(["oldest", "newest", "shortest-path", "preferred-dir", "outside-downloads"])
+                .default_value("oldest")
+                .help(match lang {
+                    "en" => "Chooses which copy of duplicate files is kept",
+                    "fr" => "Choisit quelle copie des fichiers en double est conservée",
+                    _ => "Chooses which copy of duplicate files is kept",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("prefer-dir")
+                .long("prefer-dir")
+                .value_name("DIRECTORY")
+                .help(match lang {
+                    "en" => "Directory whose copies are kept with --keep preferred-dir",
+                    "fr" => "Répertoire dont les copies sont conservées avec --keep preferred-dir",
+                    _ => "Directory whose copies are kept with --keep preferred-dir",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("image-hash")
+                .long("image-hash")
+                .value_name("ALGORITHM")
+                .value_parser(["ahash", "dhash", "phash"])
+                .default_value("phash")
+                .help(match lang {
+                    "en" => "Perceptual hash used to find similar images",
+                    "fr" => "Hachage perceptuel utilisé pour trouver les images similaires",
+                    _ => "Perceptual hash used to find similar images",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("image-distance")
+                .long("image-distance")
+                .value_name("BITS")
+                .value_parser(clap::value_parser!(u32).range(0..=64))
+                .default_value("8")
+                .help(match lang {
+                    "en" => "Maximum Hamming distance between the hashes of similar images",
+                    "fr" => "Distance de Hamming maximale entre les hachages d'images similaires",
+                    _ => "Maximum Hamming distance between the hashes of similar images",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("top")
+                .long("top")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+                .help(match lang {
+                    "en" => "Number of files and directories listed by the disk usage analysis",
+                    "fr" => "Nombre de fichiers et de répertoires listés par l'analyse de l'espace disque",
+                    _ => "Number of files and directories listed by the disk usage analysis",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("ext")
+                .long("ext")
+                .value_name("EXTENSIONS")
+                .num_args(1..)
+                .help(match lang {
+                    "en" => "Only counts files with these extensions in the disk usage analysis and the empty file search",
+                    "fr" => "Ne compte que les fichiers avec ces extensions dans l'analyse de l'espace disque et la recherche de fichiers vides",
+                    _ => "Only counts files with these extensions in the disk usage analysis and the empty file search",
+                }))
+            .arg(Arg::new("where")
+                .long("where")
+                .value_name("EXPRESSION")
+                .help(match lang {
+                    "en" => "Only selects files matching this expression in every scan, e.g. 'ext in (log,tmp) and size > 100M and age > 30d'",
+                    "fr" => "Ne sélectionne que les fichiers correspondant à cette expression dans toutes les analyses, ex. 'ext in (log,tmp) and size > 100M and age > 30d'",
+                    _ => "Only selects files matching this expression in every scan, e.g. 'ext in (log,tmp) and size > 100M and age > 30d'",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("min-size")
+                .long("min-size")
+                .value_name("SIZE")
+                .value_parser(size::parse_size)
+                .help(match lang {
+                    "en" => "Only selects files of at least this size (e.g. 500K, 100M, 2G) in every scan",
+                    "fr" => "Ne sélectionne que les fichiers d'au moins cette taille (ex. 500K, 100M, 2G) dans toutes les analyses",
+                    _ => "Only selects files of at least this size (e.g. 500K, 100M, 2G) in every scan",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("max-size")
+                .long("max-size")
+                .value_name("SIZE")
+                .value_parser(size::parse_size)
+                .help(match lang {
+                    "en" => "Only selects files of at most this size in every scan",
+                    "fr" => "Ne sélectionne que les fichiers d'au plus cette taille dans toutes les analyses",
+                    _ => "Only selects files of at most this size in every scan",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("older-than")
+                .long("older-than")
+                .value_name("AGE")
+                .value_parser(age::parse_duration)
+                .help(match lang {
+                    "en" => "Only counts files older than this age (e.g. 90m, 30d, 2w, 6mo) in the disk usage analysis",
+                    "fr" => "Ne compte que les fichiers plus anciens que cet âge (ex. 90m, 30d, 2w, 6mo) dans l'analyse de l'espace disque",
+                    _ => "Only counts files older than this age (e.g. 90m, 30d, 2w, 6mo) in the disk usage analysis",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("newer-than")
+                .long("newer-than")
+                .value_name("AGE")
+                .value_parser(age::parse_duration)
+                .help(match lang {
+                    "en" => "Only counts files newer than this age in the disk usage analysis",
+                    "fr" => "Ne compte que les fichiers plus récents que cet âge dans l'analyse de l'espace disque",
+                    _ => "Only counts files newer than this age in the disk usage analysis",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("time-field")
+                .long("time-field")
+                .value_name("FIELD")
+                .value_parser(["mtime", "atime", "ctime", "btime"])
+                .default_value("mtime")
+                .help(match lang {
+                    "en" => "Timestamp compared by age rules: mtime (modified), atime (accessed), ctime (status changed) or btime (created)",
+                    "fr" => "Horodatage utilisé par les règles d'âge : mtime (modification), atime (accès), ctime (changement d'état) ou btime (création)",
+                    _ => "Timestamp compared by age rules: mtime (modified), atime (accessed), ctime (status changed) or btime (created)",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("allocated")
+                .long("allocated")
+                .help(match lang {
+                    "en" => "Ranks by space allocated on disk instead of apparent size",
+                    "fr" => "Classe selon l'espace alloué sur le disque plutôt que la taille apparente",
+                    _ => "Ranks by space allocated on disk instead of apparent size",
+                })
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("remove-empty-dirs")
+                .long("remove-empty-dirs")
+                .help(match lang {
+                    "en" => "Removes the directories emptied by cleaning",
+                    "fr" => "Supprime les répertoires vidés par le nettoyage",
+                    _ => "Removes the directories emptied by cleaning",
+                })
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help(match lang {
+                    "en" => "Configuration file (default: ~/.config/rustcleaner/config.json)",
+                    "fr" => "Fichier de configuration (par défaut : ~/.config/rustcleaner/config.json)",
+                    _ => "Configuration file (default: ~/.config/rustcleaner/config.json)",
+                })
+                .action(clap::ArgAction::Set))
+            .arg(Arg::new("protect")
+                .long("protect")
+                .value_name("PATH")
+                .num_args(1..)
+                .help(match lang {
+                    "en" => "Never deletes anything in these paths",
+                    "fr" => "Ne supprime jamais rien dans ces chemins",
+                    _ => "Never deletes anything in these paths",
+                }))
+            .arg(Arg::new("allow-path")
+                .long("allow-path")
+                .value_name("PATH")
+                .num_args(1..)
+                .help(match lang {
+                    "en" => "Allows deleting in these paths even if a built-in rule protects them",
+                    "fr" => "Autorise la suppression dans ces chemins même si une règle intégrée les protège",
+                    _ => "Allows deleting in these paths even if a built-in rule protects them",
+                }))
+            .arg(Arg::new("one-file-system")
+                .short('x')
+                .long("one-file-system")
+                .help(match lang {
+                    "en" => "Stays on the filesystem of each scanned directory",
+                    "fr" => "Reste sur le système de fichiers de chaque répertoire analysé",
+                    _ => "Stays on the filesystem of each scanned directory",
+                })
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("scan-network-mounts")
+                .long("scan-network-mounts")
+                .help(match lang {
+                    "en" => "Also scans network and pseudo filesystems mounted inside the scanned directories",
+                    "fr" => "Analyse aussi les systèmes de fichiers réseau et virtuels montés dans les répertoires analysés",
+                    _ => "Also scans network and pseudo filesystems mounted inside the scanned directories",
+                })
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("delete-open-files")
+                .long("delete-open-files")
+                .help(match lang {
+                    "en" => "Also deletes files that running processes have open",
+                    "fr" => "Supprime aussi les fichiers ouverts par des processus en cours",
+                    _ => "Also deletes files that running processes have open",
+                })
+                .action(clap::ArgAction::SetTrue))
             .arg(Arg::new("exclude")
                 .short('e')
                 .long("exclude")
@@ -337,6 +663,52 @@ async fn main() {
                 .action(clap::ArgAction::Set))
             .get_matches();
 
+        // Chargement de la configuration et des chemins protégés
+        let config_path = matches.get_one::<String>("config").map(PathBuf::from).unwrap_or_else(config::default_path);
+        // Sans la configuration, ses chemins protégés seraient perdus : on s'arrête.
+        let mut config = config::Config::load(&config_path).unwrap_or_else(|e| {
+            eprintln!("{} {}", match lang {
+                "en" => "Invalid configuration file, nothing was done:",
+                "fr" => "Fichier de configuration invalide, aucune action effectuée :",
+                _ => "Invalid configuration file, nothing was done:",
+            }, e);
+            std::process::exit(1);
+        });
+        for e in config.rule_errors() {
+            eprintln!("{} {}", match lang {
+                "en" => "Invalid rule in the configuration file:",
+                "fr" => "Règle invalide dans le fichier de configuration :",
+                _ => "Invalid rule in the configuration file:",
+            }, e);
+        }
+        config.protect.deny.extend(matches.get_many::<String>("protect").unwrap_or_default().map(PathBuf::from));
+        config.protect.allow.extend(matches.get_many::<String>("allow-path").unwrap_or_default().map(PathBuf::from));
+        protect::set_policy(protect::Policy::builtin().with_config(&config.protect));
+        open_files::set_skip_open_files(!matches.get_flag("delete-open-files"));
+        mounts::set_walk_policy(mounts::WalkPolicy::new(
+            matches.get_flag("one-file-system"),
+            !matches.get_flag("scan-network-mounts"),
+        ));
+        size::set_size_filter(size::SizeFilter {
+            min: matches.get_one::<u64>("min-size").copied(),
+            max: matches.get_one::<u64>("max-size").copied(),
+        });
+        let where_filter = match matches.get_one::<String>("where") {
+            Some(text) => match filter::Filter::parse(text, &config.rules) {
+                Ok(filter) => Some(filter),
+                Err(e) => {
+                    eprintln!("{} {}", match lang {
+                        "en" => "Invalid --where expression:",
+                        "fr" => "Expression --where invalide :",
+                        _ => "Invalid --where expression:",
+                    }, e);
+                    std::process::exit(1);
+                }
+            },
+            None => None,
+        };
+        filter::set_filter(where_filter.clone());
+
         // Collecte des répertoires à exclure
         let mut exclude_dirs: HashSet<String> = matches.get_many::<String>("exclude")
             .unwrap_or_default()
@@ -388,7 +760,8 @@ async fn main() {
                         .unwrap();
 
                     if proceed {
-                        cleaner::clean_files(files_to_clean, matches.get_flag("secure-clean"));
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
                     } else {
                         println!("{}", match lang {
                             "en" => "Operation cancelled by user.",
@@ -421,7 +794,8 @@ async fn main() {
                         .unwrap();
 
                     if proceed {
-                        cleaner::clean_files(files_to_clean, matches.get_flag("secure-clean"));
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
                     } else {
                         println!("{}", match lang {
                             "en" => "Operation cancelled by user.",
@@ -453,7 +827,8 @@ async fn main() {
                         let downloads_dir = dirs::download_dir().unwrap();
                     #[cfg(not(target_os = "windows"))]
                         let downloads_dir = dirs::home_dir().unwrap().join("Downloads");
-                    cleaner::clear_directory(downloads_dir);
+                    let deleted = cleaner::clear_directory(&downloads_dir);
+                    remove_empty_dirs_after_clean(&matches, &deleted, &[&downloads_dir], &exclude_dirs);
                 } else {
                     println!("{}", match lang {
                         "en" => "Operation cancelled by user.",
@@ -561,11 +936,12 @@ async fn main() {
                     let exclude_dirs = exclude_dirs.clone();
                     let exclude_types = exclude_types.clone();
                     let secure_clean = matches.get_flag("secure-clean");
+                    let remove_empty = matches.get_flag("remove-empty-dirs");
 
                     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
 
                     task::spawn(async move {
-                        run_scheduled_job(schedule_expression, dirs_to_scan, exclude_dirs, exclude_types, secure_clean, shutdown_rx).await;
+                        run_scheduled_job(schedule_expression, dirs_to_scan, exclude_dirs, exclude_types, secure_clean, remove_empty, shutdown_rx).await;
                     });
 
                     println!("{}", match lang {
@@ -592,22 +968,65 @@ async fn main() {
             },
             // Analyser et supprimer les fichiers en double
             6 => {
-                let files_to_clean = cleaner::find_duplicate_files(&dirs_to_scan, &exclude_dirs, &exclude_types);
+                let duplicate_options = match duplicate_options(&matches) {
+                    Ok(options) => options,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        continue;
+                    }
+                };
+                let duplicate_groups = cleaner::find_duplicate_files(&dirs_to_scan, &exclude_dirs, &exclude_types, &duplicate_options);
 
-                if !files_to_clean.is_empty() {
-                    cleaner::print_duplicates_report(&files_to_clean);
+                if !duplicate_groups.is_empty() {
+                    println!("{} {}", match lang {
+                        "en" => "Keep policy:",
+                        "fr" => "Politique de conservation :",
+                        _ => "Keep policy:",
+                    }, duplicate_options.keep.name());
+                    cleaner::print_duplicates_report(&duplicate_groups);
+                    let files_to_clean: Vec<PathBuf> = duplicate_groups.iter()
+                        .flat_map(|group| group.redundant().cloned())
+                        .collect();
 
-                    let proceed = Confirm::new()
+                    let actions = match lang {
+                        "fr" => &[
+                            "Supprimer les doublons",
+                            "Remplacer les doublons par des liens physiques",
+                            "Remplacer les doublons par des clones reflink",
+                            "Remplacer par des clones reflink, sinon des liens physiques",
+                            "Annuler",
+                        ],
+                        _ => &[
+                            "Delete the duplicates",
+                            "Replace the duplicates with hardlinks",
+                            "Replace the duplicates with reflink clones",
+                            "Replace with reflink clones, falling back to hardlinks",
+                            "Cancel",
+                        ],
+                    };
+                    let action = Select::new()
                         .with_prompt(match lang {
-                            "en" => "Do you want to delete these duplicate files?",
-                            "fr" => "Voulez-vous supprimer ces fichiers en double?",
-                            _ => "Do you want to delete these duplicate files?",
+                            "en" => "What do you want to do with these duplicate files?",
+                            "fr" => "Que voulez-vous faire de ces fichiers en double?",
+                            _ => "What do you want to do with these duplicate files?",
                         })
+                        .items(actions)
+                        .default(0)
                         .interact()
                         .unwrap();
 
-                    if proceed {
-                        cleaner::clean_files(files_to_clean, matches.get_flag("secure-clean"));
+                    let link_mode = match action {
+                        1 => Some(dedupe::LinkMode::Hardlink),
+                        2 => Some(dedupe::LinkMode::Reflink),
+                        3 => Some(dedupe::LinkMode::Auto),
+                        _ => None,
+                    };
+                    if action == 0 {
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
+                    } else if let Some(link_mode) = link_mode {
+                        let outcomes = dedupe::link_duplicates(&duplicate_groups, link_mode);
+                        dedupe::print_link_report(&outcomes);
                     } else {
                         println!("{}", match lang {
                             "en" => "Operation cancelled by user.",
@@ -628,19 +1047,23 @@ async fn main() {
                 let files_to_clean = cleaner::scan_files(&dirs_to_scan, &exclude_dirs, &exclude_types);
 
                 if !files_to_clean.is_empty() {
-                    for file in &files_to_clean {
-                        let proceed = Confirm::new()
-                            .with_prompt(format!("{} {}", match lang {
-                                "en" => "Do you want to delete this file: {}?",
-                                "fr" => "Voulez-vous supprimer ce fichier : {}?",
-                                _ => "Do you want to delete this file: {}?",
-                            }, file.display()))
-                            .interact()
-                            .unwrap();
-
-                        if proceed {
-                            cleaner::clean_files(vec![file.clone()], matches.get_flag("secure-clean"));
+                    match picker::pick(files_to_clean) {
+                        Ok(Some(selected)) => {
+                            cleaner::clean_files(selected.clone(), matches.get_flag("secure-clean"));
+                            remove_empty_dirs_after_clean(&matches, &selected, &dirs_to_scan, &exclude_dirs);
                         }
+                        Ok(None) => {
+                            println!("{}", match lang {
+                                "en" => "Operation cancelled by user.",
+                                "fr" => "Opération annulée par l'utilisateur.",
+                                _ => "Operation cancelled by user.",
+                            });
+                        }
+                        Err(e) => eprintln!("{} {}", match lang {
+                            "en" => "Failed to start the selection list:",
+                            "fr" => "Impossible d'afficher la liste de sélection :",
+                            _ => "Failed to start the selection list:",
+                        }, e),
                     }
                 } else {
                     println!("{}", match lang {
@@ -652,22 +1075,35 @@ async fn main() {
             },
             // Nettoyer les fichiers de cache du navigateur
             8 => {
+                let profiles = browsers::discover();
+                if profiles.is_empty() {
+                    println!("{}", match lang {
+                        "en" => "No browser profiles found.",
+                        "fr" => "Aucun profil de navigateur trouvé.",
+                        _ => "No browser profiles found.",
+                    });
+                    continue;
+                }
+                browsers::print_profiles_report(&profiles);
+                if profiles.iter().any(|p| p.in_use) {
+                    eprintln!("{}", match lang {
+                        "en" => "Warning: some browsers are running; their profiles will be skipped. Close them to clean their cache.",
+                        "fr" => "Attention : certains navigateurs sont ouverts ; leurs profils seront ignorés. Fermez-les pour nettoyer leur cache.",
+                        _ => "Warning: some browsers are running; their profiles will be skipped. Close them to clean their cache.",
+                    });
+                }
+                let total: u64 = profiles.iter().filter(|p| !p.in_use).map(|p| p.size).sum();
                 let proceed = Confirm::new()
-                    .with_prompt(match lang {
-                        "en" => "Do you want to clean browser cache files?",
-                        "fr" => "Voulez-vous nettoyer les fichiers de cache du navigateur?",
-                        _ => "Do you want to clean browser cache files?",
-                    })
+                    .with_prompt(format!("{} ({})?", match lang {
+                        "en" => "Do you want to clean browser cache files",
+                        "fr" => "Voulez-vous nettoyer les fichiers de cache du navigateur",
+                        _ => "Do you want to clean browser cache files",
+                    }, usage::human_size(total)))
                     .interact()
                     .unwrap();
 
                 if proceed {
-                    cleaner::clean_browser_cache();
-                    println!("{}", match lang {
-                        "en" => "Browser cache cleaned.",
-                        "fr" => "Cache du navigateur nettoyé.",
-                        _ => "Browser cache cleaned.",
-                    });
+                    browsers::clean_profiles(&profiles);
                 } else {
                     println!("{}", match lang {
                         "en" => "Operation cancelled by user.",
@@ -719,7 +1155,8 @@ async fn main() {
                         .unwrap();
 
                     if proceed {
-                        cleaner::secure_clean_files(files_to_clean);
+                        cleaner::secure_clean_files(files_to_clean.clone());
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
                     } else {
                         println!("{}", match lang {
                             "en" => "Operation cancelled by user.",
@@ -735,18 +1172,417 @@ async fn main() {
                     });
                 }
             },
-            // Nettoyer les fichiers plus anciens qu'un nombre de jours spécifié
+            // Nettoyer les fichiers selon leur âge
             11 => {
-                let age_in_days: i64 = Input::new()
+                let older_than: String = Input::new()
                     .with_prompt(match lang {
-                        "en" => "Enter the number of days",
-                        "fr" => "Entrez le nombre de jours",
-                        _ => "Enter the number of days",
+                        "en" => "Enter the minimum age (e.g. 90m, 30d, 2w, 6mo; a number alone is days)",
+                        "fr" => "Entrez l'âge minimum (ex. 90m, 30d, 2w, 6mo ; un nombre seul compte des jours)",
+                        _ => "Enter the minimum age (e.g. 90m, 30d, 2w, 6mo; a number alone is days)",
                     })
+                    .validate_with(|input: &String| age::parse_duration(input).map(|_| ()))
                     .interact_text()
                     .unwrap();
+                let newer_than: String = Input::new()
+                    .with_prompt(match lang {
+                        "en" => "Enter the maximum age (leave empty for no limit)",
+                        "fr" => "Entrez l'âge maximum (laisser vide pour aucune limite)",
+                        _ => "Enter the maximum age (leave empty for no limit)",
+                    })
+                    .allow_empty(true)
+                    .validate_with(|input: &String| if input.trim().is_empty() { Ok(()) } else { age::parse_duration(input).map(|_| ()) })
+                    .interact_text()
+                    .unwrap();
+                let rule = age::AgeRule {
+                    field: age::TimeField::from_name(matches.get_one::<String>("time-field").unwrap()).unwrap(),
+                    older_than: age::parse_duration(&older_than).ok(),
+                    newer_than: age::parse_duration(&newer_than).ok(),
+                };
+                age::warn_unreliable_atime(&rule, &dirs_to_scan);
+
+                let files_to_clean = cleaner::scan_files_for_age(&dirs_to_scan, &exclude_dirs, &rule);
+
+                if !files_to_clean.is_empty() {
+                    cleaner::report_clean(files_to_clean.clone(), start_time);
+
+                    let proceed = Confirm::new()
+                        .with_prompt(match lang {
+                            "en" => "Do you want to delete the above files?",
+                            "fr" => "Voulez-vous supprimer les fichiers ci-dessus?",
+                            _ => "Do you want to delete the above files?",
+                        })
+                        .interact()
+                        .unwrap();
+
+                    if proceed {
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No files to clean.",
+                        "fr" => "Aucun fichier à nettoyer.",
+                        _ => "No files to clean.",
+                    });
+                }
+            },
+            // Purger le cache de hachage des doublons
+            12 => {
+                let mut cache = hash_cache::HashCache::load(&hash_cache::default_path());
+                let removed = cache.prune();
+                match cache.save() {
+                    Ok(()) => println!("{} {} / {}", match lang {
+                        "en" => "Stale hash cache entries removed:",
+                        "fr" => "Entrées obsolètes supprimées du cache de hachage :",
+                        _ => "Stale hash cache entries removed:",
+                    }, removed, removed + cache.len()),
+                    Err(e) => eprintln!("{} {}", match lang {
+                        "en" => "Failed to save the hash cache:",
+                        "fr" => "Échec de l'enregistrement du cache de hachage :",
+                        _ => "Failed to save the hash cache:",
+                    }, e),
+                }
+            },
+            // Rechercher les arborescences de répertoires en double
+            13 => {
+                let duplicate_options = match duplicate_options(&matches) {
+                    Ok(options) => options,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        continue;
+                    }
+                };
+                let tree_groups = cleaner::find_duplicate_trees(&dirs_to_scan, &exclude_dirs, &duplicate_options);
+
+                if !tree_groups.is_empty() {
+                    cleaner::print_duplicate_trees_report(&tree_groups);
+
+                    let proceed = Confirm::new()
+                        .with_prompt(match lang {
+                            "en" => "Do you want to delete the duplicate directory trees?",
+                            "fr" => "Voulez-vous supprimer les arborescences en double?",
+                            _ => "Do you want to delete the duplicate directory trees?",
+                        })
+                        .interact()
+                        .unwrap();
+
+                    if proceed {
+                        cleaner::remove_duplicate_trees(&tree_groups);
+                        let deleted: Vec<PathBuf> = tree_groups.iter().flat_map(|g| g.redundant()).cloned().collect();
+                        remove_empty_dirs_after_clean(&matches, &deleted, &dirs_to_scan, &exclude_dirs);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No duplicate directory trees found.",
+                        "fr" => "Aucune arborescence en double trouvée.",
+                        _ => "No duplicate directory trees found.",
+                    });
+                }
+            },
+            // Rechercher les images similaires
+            14 => {
+                let algorithm = similar_images::HashAlgorithm::from_name(matches.get_one::<String>("image-hash").unwrap()).unwrap();
+                let max_distance = *matches.get_one::<u32>("image-distance").unwrap();
+                let groups = similar_images::find_similar_images(&dirs_to_scan, &exclude_dirs, algorithm, max_distance);
+
+                if !groups.is_empty() {
+                    similar_images::print_similar_images_report(&groups);
+
+                    // Chaque image à supprimer est choisie, groupe par groupe.
+                    let candidates: Vec<(usize, &similar_images::ImageInfo)> = groups.iter()
+                        .enumerate()
+                        .flat_map(|(index, group)| group.others().map(move |image| (index, image)))
+                        .collect();
+                    let items: Vec<String> = candidates.iter()
+                        .map(|(index, image)| format!("{} {}: {} ({}x{}, {} {})", match lang {
+                            "fr" => "Groupe",
+                            _ => "Group",
+                        }, index + 1, image.path.display(), image.width, image.height, match lang {
+                            "fr" => "proche de",
+                            _ => "similar to",
+                        }, groups[*index].best().path.display()))
+                        .collect();
+                    let selected = MultiSelect::new()
+                        .with_prompt(match lang {
+                            "en" => "Select the images to delete; the best copy of each group is kept (space to select, enter to confirm)",
+                            "fr" => "Sélectionnez les images à supprimer ; la meilleure copie de chaque groupe est conservée (espace pour sélectionner, entrée pour valider)",
+                            _ => "Select the images to delete; the best copy of each group is kept (space to select, enter to confirm)",
+                        })
+                        .items(&items)
+                        .interact()
+                        .unwrap();
+
+                    if !selected.is_empty() {
+                        let files_to_clean: Vec<PathBuf> = selected.into_iter()
+                            .map(|i| candidates[i].1.path.clone())
+                            .collect();
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No similar images found.",
+                        "fr" => "Aucune image similaire trouvée.",
+                        _ => "No similar images found.",
+                    });
+                }
+            },
+            // Supprimer les répertoires vides
+            15 => {
+                let proceed = Confirm::new()
+                    .with_prompt(match lang {
+                        "en" => "Do you want to remove the empty directories in the scanned folders?",
+                        "fr" => "Voulez-vous supprimer les répertoires vides des dossiers analysés?",
+                        _ => "Do you want to remove the empty directories in the scanned folders?",
+                    })
+                    .interact()
+                    .unwrap();
+
+                if proceed {
+                    cleaner::remove_empty_dirs(&dirs_to_scan, &exclude_dirs);
+                } else {
+                    println!("{}", match lang {
+                        "en" => "Operation cancelled by user.",
+                        "fr" => "Opération annulée par l'utilisateur.",
+                        _ => "Operation cancelled by user.",
+                    });
+                }
+            },
+            // Rechercher les liens symboliques cassés
+            16 => {
+                let broken = symlinks::find_broken_symlinks(&dirs_to_scan, &exclude_dirs);
+
+                if !broken.is_empty() {
+                    symlinks::print_broken_symlinks_report(&broken);
+
+                    let proceed = Confirm::new()
+                        .with_prompt(match lang {
+                            "en" => "Do you want to delete these broken links?",
+                            "fr" => "Voulez-vous supprimer ces liens cassés?",
+                            _ => "Do you want to delete these broken links?",
+                        })
+                        .interact()
+                        .unwrap();
+
+                    if proceed {
+                        let files_to_clean: Vec<PathBuf> = broken.into_iter().map(|link| link.path).collect();
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No broken symbolic links found.",
+                        "fr" => "Aucun lien symbolique cassé trouvé.",
+                        _ => "No broken symbolic links found.",
+                    });
+                }
+            },
+            // Rechercher les fichiers et répertoires les plus volumineux
+            17 => {
+                let options = usage::UsageOptions {
+                    top: *matches.get_one::<usize>("top").unwrap(),
+                    extensions: matches.get_many::<String>("ext")
+                        .unwrap_or_default()
+                        .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                        .collect(),
+                    age: age_rule(&matches),
+                    by_allocated: matches.get_flag("allocated"),
+                };
+                if let Some(rule) = &options.age {
+                    age::warn_unreliable_atime(rule, &dirs_to_scan);
+                }
+                let report = usage::analyze(&dirs_to_scan, &exclude_dirs, &options);
 
-                let files_to_clean = cleaner::scan_files_for_age(&dirs_to_scan, &exclude_dirs, age_in_days);
+                if !report.files.is_empty() {
+                    usage::print_usage_report(&report);
+
+                    let items: Vec<String> = report.files.iter()
+                        .map(|f| format!("{} ({})", f.path.display(), usage::human_size(f.apparent)))
+                        .chain(report.dirs.iter().map(|d| format!("{}/ ({})", d.path.display(), usage::human_size(d.apparent))))
+                        .collect();
+                    let selected = MultiSelect::new()
+                        .with_prompt(match lang {
+                            "en" => "Select the files and directories to clean (space to select, enter to confirm)",
+                            "fr" => "Sélectionnez les fichiers et répertoires à nettoyer (espace pour sélectionner, entrée pour valider)",
+                            _ => "Select the files and directories to clean (space to select, enter to confirm)",
+                        })
+                        .items(&items)
+                        .interact()
+                        .unwrap();
+
+                    // Un répertoire sélectionné envoie ses fichiers correspondant aux filtres.
+                    let mut files_to_clean: Vec<PathBuf> = Vec::new();
+                    for index in selected {
+                        if let Some(file) = report.files.get(index) {
+                            files_to_clean.push(file.path.clone());
+                        } else {
+                            let dir = &report.dirs[index - report.files.len()];
+                            files_to_clean.extend(usage::matching_files(&dir.path, &exclude_dirs, &options));
+                        }
+                    }
+                    files_to_clean.sort();
+                    files_to_clean.dedup();
+
+                    if files_to_clean.is_empty() {
+                        println!("{}", match lang {
+                            "en" => "Nothing selected.",
+                            "fr" => "Rien n'a été sélectionné.",
+                            _ => "Nothing selected.",
+                        });
+                        continue;
+                    }
+                    let total: u64 = files_to_clean.iter().filter_map(|f| std::fs::symlink_metadata(f).ok()).map(|m| m.len()).sum();
+                    let proceed = Confirm::new()
+                        .with_prompt(format!("{} {} ({})?", match lang {
+                            "en" => "Do you want to delete the selected files:",
+                            "fr" => "Voulez-vous supprimer les fichiers sélectionnés :",
+                            _ => "Do you want to delete the selected files:",
+                        }, files_to_clean.len(), usage::human_size(total)))
+                        .interact()
+                        .unwrap();
+
+                    if proceed {
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No files found.",
+                        "fr" => "Aucun fichier trouvé.",
+                        _ => "No files found.",
+                    });
+                }
+            },
+            // Explorer l'espace disque de façon interactive
+            18 => {
+                let tree = tui::Tree::build(&dirs_to_scan, &exclude_dirs, &exclude_types);
+                match tui::explore(tree) {
+                    Ok(Some((action, selection))) => {
+                        match action {
+                            tui::Action::Delete => cleaner::clean_files(selection.files, matches.get_flag("secure-clean")),
+                            tui::Action::SecureDelete => {
+                                cleaner::secure_clean_files(selection.files);
+                            }
+                            tui::Action::Trash => cleaner::trash_files(selection.items.clone()),
+                        }
+                        // Les dossiers marqués sont supprimés une fois vidés.
+                        if action != tui::Action::Trash {
+                            cleaner::remove_empty_dirs(&selection.dirs, &exclude_dirs);
+                            for dir in &selection.dirs {
+                                match safe_delete::remove_dir(dir) {
+                                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                                        cleaner::deletion_failure(dir, &e);
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            remove_empty_dirs_after_clean(&matches, &selection.items, &dirs_to_scan, &exclude_dirs);
+                        }
+                    }
+                    Ok(None) => {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                    Err(e) => eprintln!("{} {}", match lang {
+                        "en" => "Failed to start the explorer:",
+                        "fr" => "Impossible de lancer l'explorateur :",
+                        _ => "Failed to start the explorer:",
+                    }, e),
+                }
+            },
+            // Rechercher les fichiers vides
+            19 => {
+                let extensions: HashSet<String> = matches.get_many::<String>("ext")
+                    .unwrap_or_default()
+                    .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                    .collect();
+                let rule = age_rule(&matches);
+                if let Some(rule) = &rule {
+                    age::warn_unreliable_atime(rule, &dirs_to_scan);
+                }
+                let empty_files = size::find_empty_files(&dirs_to_scan, &exclude_dirs, &exclude_types, &extensions, rule.as_ref());
+
+                if !empty_files.is_empty() {
+                    size::print_empty_files_report(&empty_files);
+
+                    let proceed = Confirm::new()
+                        .with_prompt(match lang {
+                            "en" => "Do you want to delete these empty files?",
+                            "fr" => "Voulez-vous supprimer ces fichiers vides?",
+                            _ => "Do you want to delete these empty files?",
+                        })
+                        .interact()
+                        .unwrap();
+
+                    if proceed {
+                        cleaner::clean_files(empty_files.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &empty_files, &dirs_to_scan, &exclude_dirs);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No empty files found.",
+                        "fr" => "Aucun fichier vide trouvé.",
+                        _ => "No empty files found.",
+                    });
+                }
+            },
+            // Nettoyer les fichiers correspondant à une expression de filtre
+            20 => {
+                let filter = match where_filter {
+                    Some(filter) => filter,
+                    None => {
+                        let text: String = Input::new()
+                            .with_prompt(match lang {
+                                "en" => "Enter a filter expression (e.g. ext in (log,tmp) and size > 100M and age > 30d)",
+                                "fr" => "Entrez une expression de filtre (ex. ext in (log,tmp) and size > 100M and age > 30d)",
+                                _ => "Enter a filter expression (e.g. ext in (log,tmp) and size > 100M and age > 30d)",
+                            })
+                            .validate_with(|input: &String| filter::Filter::parse(input, &config.rules).map(|_| ()))
+                            .interact_text()
+                            .unwrap();
+                        filter::Filter::parse(&text, &config.rules).unwrap()
+                    }
+                };
+                let files_to_clean = filter::find_matching_files(&dirs_to_scan, &exclude_dirs, &filter);
 
                 if !files_to_clean.is_empty() {
                     cleaner::report_clean(files_to_clean.clone(), start_time);
@@ -761,7 +1597,8 @@ async fn main() {
                         .unwrap();
 
                     if proceed {
-                        cleaner::clean_files(files_to_clean, matches.get_flag("secure-clean"));
+                        cleaner::clean_files(files_to_clean.clone(), matches.get_flag("secure-clean"));
+                        remove_empty_dirs_after_clean(&matches, &files_to_clean, &dirs_to_scan, &exclude_dirs);
                     } else {
                         println!("{}", match lang {
                             "en" => "Operation cancelled by user.",
@@ -777,6 +1614,273 @@ async fn main() {
                     });
                 }
             },
+            // Nettoyer les artefacts de compilation des projets inactifs
+            21 => {
+                let inactive_for: String = Input::new()
+                    .with_prompt(match lang {
+                        "en" => "Only clean projects untouched for at least (e.g. 30d, 6mo)",
+                        "fr" => "Ne nettoyer que les projets inactifs depuis au moins (ex. 30d, 6mo)",
+                        _ => "Only clean projects untouched for at least (e.g. 30d, 6mo)",
+                    })
+                    .default("90d".to_string())
+                    .validate_with(|input: &String| age::parse_duration(input).map(|_| ()))
+                    .interact_text()
+                    .unwrap();
+                let inactive_for = age::parse_duration(&inactive_for).unwrap();
+                let now = chrono::Utc::now();
+                let projects = projects::find_projects(&dirs_to_scan, &exclude_dirs);
+
+                if !projects.is_empty() {
+                    projects::print_projects_report(&projects, inactive_for, now);
+                    let stale: Vec<projects::Project> = projects.into_iter().filter(|p| p.is_stale(inactive_for, now)).collect();
+                    if stale.is_empty() {
+                        println!("{}", match lang {
+                            "en" => "No inactive projects to clean.",
+                            "fr" => "Aucun projet inactif à nettoyer.",
+                            _ => "No inactive projects to clean.",
+                        });
+                        continue;
+                    }
+
+                    let total: u64 = stale.iter().map(|p| p.artifact_size()).sum();
+                    let proceed = Confirm::new()
+                        .with_prompt(format!("{} {} ({})?", match lang {
+                            "en" => "Do you want to delete the build artifacts of the inactive projects:",
+                            "fr" => "Voulez-vous supprimer les artefacts de compilation des projets inactifs :",
+                            _ => "Do you want to delete the build artifacts of the inactive projects:",
+                        }, stale.len(), usage::human_size(total)))
+                        .interact()
+                        .unwrap();
+
+                    if proceed {
+                        projects::remove_artifacts(&stale);
+                    } else {
+                        println!("{}", match lang {
+                            "en" => "Operation cancelled by user.",
+                            "fr" => "Opération annulée par l'utilisateur.",
+                            _ => "Operation cancelled by user.",
+                        });
+                    }
+                } else {
+                    println!("{}", match lang {
+                        "en" => "No build artifacts found.",
+                        "fr" => "Aucun artefact de compilation trouvé.",
+                        _ => "No build artifacts found.",
+                    });
+                }
+            },
+            // Nettoyer les caches de Cargo et rustup
+            22 => {
+                let (Some(cargo_home), Some(rustup_home)) = (rust_cache::cargo_home(), rust_cache::rustup_home()) else {
+                    println!("{}", match lang {
+                        "en" => "Home directory not found.",
+                        "fr" => "Répertoire personnel introuvable.",
+                        _ => "Home directory not found.",
+                    });
+                    continue;
+                };
+                let toolchains = rust_cache::toolchains(&rustup_home);
+                rust_cache::print_cache_report(&rust_cache::cache_areas(&cargo_home, &rustup_home), &toolchains);
+
+                // Archives et checkouts inutilisés
+                let unused_for: String = Input::new()
+                    .with_prompt(match lang {
+                        "en" => "Prune crate archives and git checkouts not used for (e.g. 30d, 6mo)",
+                        "fr" => "Purger les archives de crates et checkouts git inutilisés depuis (ex. 30d, 6mo)",
+                        _ => "Prune crate archives and git checkouts not used for (e.g. 30d, 6mo)",
+                    })
+                    .default("90d".to_string())
+                    .validate_with(|input: &String| age::parse_duration(input).map(|_| ()))
+                    .interact_text()
+                    .unwrap();
+                let unused_for = age::parse_duration(&unused_for).unwrap();
+                let rule = age::AgeRule { field: age::TimeField::Accessed, older_than: Some(unused_for), newer_than: None };
+                age::warn_unreliable_atime(&rule, &[&cargo_home]);
+                let stale = rust_cache::stale_entries(&cargo_home, unused_for, chrono::Utc::now());
+                if !stale.is_empty() {
+                    rust_cache::print_entries_report(&stale);
+                    let total: u64 = stale.iter().map(|e| e.size).sum();
+                    let proceed = Confirm::new()
+                        .with_prompt(format!("{} {} ({})?", match lang {
+                            "en" => "Do you want to delete the unused archives and checkouts:",
+                            "fr" => "Voulez-vous supprimer les archives et checkouts inutilisés :",
+                            _ => "Do you want to delete the unused archives and checkouts:",
+                        }, stale.len(), usage::human_size(total)))
+                        .interact()
+                        .unwrap();
+                    if proceed {
+                        rust_cache::remove_entries(&stale);
+                    }
+                }
+
+                // Sources extraites du registre
+                let sources = rust_cache::registry_sources(&cargo_home);
+                let total: u64 = sources.iter().map(|e| e.size).sum();
+                if total > 0 {
+                    let proceed = Confirm::new()
+                        .with_prompt(format!("{} ({})?", match lang {
+                            "en" => "Do you want to delete the extracted crate sources? Cargo extracts them again when needed",
+                            "fr" => "Voulez-vous supprimer les sources de crates extraites ? Cargo les extrait de nouveau au besoin",
+                            _ => "Do you want to delete the extracted crate sources? Cargo extracts them again when needed",
+                        }, usage::human_size(total)))
+                        .interact()
+                        .unwrap();
+                    if proceed {
+                        rust_cache::remove_entries(&sources);
+                    }
+                }
+
+                // Toolchains autres que celle par défaut
+                let removable: Vec<rust_cache::Toolchain> = toolchains.into_iter().filter(|t| !t.is_default).collect();
+                if !removable.is_empty() {
+                    let items: Vec<String> = removable.iter().map(|t| format!("{} ({})", t.name, usage::human_size(t.size))).collect();
+                    let selected = MultiSelect::new()
+                        .with_prompt(match lang {
+                            "en" => "Select the toolchains to uninstall (space to select, enter to confirm)",
+                            "fr" => "Sélectionnez les toolchains à désinstaller (espace pour sélectionner, entrée pour valider)",
+                            _ => "Select the toolchains to uninstall (space to select, enter to confirm)",
+                        })
+                        .items(&items)
+                        .interact()
+                        .unwrap();
+                    let selected: Vec<rust_cache::Toolchain> = selected.into_iter().map(|i| removable[i].clone()).collect();
+                    if !selected.is_empty() {
+                        rust_cache::uninstall_toolchains(&selected);
+                    }
+                }
+            },
+            // Nettoyer les caches des gestionnaires de paquets
+            23 => {
+                let caches = package_caches::known_caches();
+                if caches.is_empty() {
+                    println!("{}", match lang {
+                        "en" => "No package manager caches found.",
+                        "fr" => "Aucun cache de gestionnaire de paquets trouvé.",
+                        _ => "No package manager caches found.",
+                    });
+                    continue;
+                }
+                package_caches::print_caches_report(&caches);
+
+                let unused_for: String = Input::new()
+                    .with_prompt(match lang {
+                        "en" => "Prune cache entries not used for (e.g. 30d, 6mo)",
+                        "fr" => "Purger les entrées de cache inutilisées depuis (ex. 30d, 6mo)",
+                        _ => "Prune cache entries not used for (e.g. 30d, 6mo)",
+                    })
+                    .default("90d".to_string())
+                    .validate_with(|input: &String| age::parse_duration(input).map(|_| ()))
+                    .interact_text()
+                    .unwrap();
+                let unused_for = age::parse_duration(&unused_for).unwrap();
+                let rule = age::AgeRule { field: age::TimeField::Accessed, older_than: Some(unused_for), newer_than: None };
+                let paths: Vec<&PathBuf> = caches.iter().map(|c| &c.path).collect();
+                age::warn_unreliable_atime(&rule, &paths);
+
+                let now = Utc::now();
+                let stale: Vec<(package_caches::Cache, Vec<package_caches::CacheEntry>)> = caches
+                    .into_iter()
+                    .map(|cache| {
+                        let entries = cache.stale_entries(unused_for, now);
+                        (cache, entries)
+                    })
+                    .filter(|(_, entries)| !entries.is_empty())
+                    .collect();
+                if stale.is_empty() {
+                    println!("{}", match lang {
+                        "en" => "No unused cache entries found.",
+                        "fr" => "Aucune entrée de cache inutilisée trouvée.",
+                        _ => "No unused cache entries found.",
+                    });
+                    continue;
+                }
+                package_caches::print_stale_report(&stale);
+
+                let items: Vec<String> = stale
+                    .iter()
+                    .map(|(cache, entries)| format!("{} {} ({})", cache.tool.name(), cache.name, usage::human_size(entries.iter().map(|e| e.size).sum())))
+                    .collect();
+                let selected = MultiSelect::new()
+                    .with_prompt(match lang {
+                        "en" => "Select the caches to prune (space to select, enter to confirm)",
+                        "fr" => "Sélectionnez les caches à purger (espace pour sélectionner, entrée pour valider)",
+                        _ => "Select the caches to prune (space to select, enter to confirm)",
+                    })
+                    .items(&items)
+                    .defaults(&vec![true; items.len()])
+                    .interact()
+                    .unwrap();
+                let selected: Vec<(package_caches::Cache, Vec<package_caches::CacheEntry>)> = selected.into_iter().map(|i| stale[i].clone()).collect();
+                if selected.is_empty() {
+                    println!("{}", match lang {
+                        "en" => "Operation cancelled by user.",
+                        "fr" => "Opération annulée par l'utilisateur.",
+                        _ => "Operation cancelled by user.",
+                    });
+                } else {
+                    package_caches::prune(&selected);
+                }
+            },
+            // Supprimer les anciennes versions des caches apt, dnf et pacman
+            24 => {
+                let packages: Vec<system_packages::PackageFile> = system_packages::Manager::ALL
+                    .into_iter()
+                    .flat_map(|manager| system_packages::find_packages(manager, manager.cache_dir()))
+                    .collect();
+                if packages.is_empty() {
+                    println!("{}", match lang {
+                        "en" => "No apt, dnf or pacman package cache found.",
+                        "fr" => "Aucun cache de paquets apt, dnf ou pacman trouvé.",
+                        _ => "No apt, dnf or pacman package cache found.",
+                    });
+                    continue;
+                }
+                let keep: usize = Input::new()
+                    .with_prompt(match lang {
+                        "en" => "Number of versions to keep per package",
+                        "fr" => "Nombre de versions à conserver par paquet",
+                        _ => "Number of versions to keep per package",
+                    })
+                    .default(3)
+                    .interact_text()
+                    .unwrap();
+                let old = system_packages::old_versions(&packages, keep);
+                system_packages::print_reclaimable_report(&packages, &old);
+                if old.is_empty() {
+                    println!("{}", match lang {
+                        "en" => "No old package versions found.",
+                        "fr" => "Aucune ancienne version de paquet trouvée.",
+                        _ => "No old package versions found.",
+                    });
+                    continue;
+                }
+                system_packages::print_old_versions_report(&old);
+                if !system_packages::is_root() {
+                    eprintln!("{}", match lang {
+                        "en" => "Warning: these caches belong to root; deleting from them requires running as root.",
+                        "fr" => "Attention : ces caches appartiennent à root ; les nettoyer nécessite d'être root.",
+                        _ => "Warning: these caches belong to root; deleting from them requires running as root.",
+                    });
+                }
+                let total: u64 = old.iter().map(|p| p.size).sum();
+                let proceed = Confirm::new()
+                    .with_prompt(format!("{} {} ({})?", match lang {
+                        "en" => "Do you want to delete the old package versions:",
+                        "fr" => "Voulez-vous supprimer les anciennes versions de paquets :",
+                        _ => "Do you want to delete the old package versions:",
+                    }, old.len(), usage::human_size(total)))
+                    .interact()
+                    .unwrap();
+                if proceed {
+                    system_packages::remove_packages(&old);
+                } else {
+                    println!("{}", match lang {
+                        "en" => "Operation cancelled by user.",
+                        "fr" => "Opération annulée par l'utilisateur.",
+                        _ => "Operation cancelled by user.",
+                    });
+                }
+            },
             // Sélection invalide
             _ => {
                 println!("{}", match lang {
@@ -786,5 +1890,8 @@ async fn main() {
                 });
             }
         }
+
+        // Points de montage ignorés pendant l'analyse
+        mounts::print_skipped_mounts();
     }
 }
@@ -0,0 +1,327 @@
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use walkdir::{DirEntry, FilterEntry, IntoIter, WalkDir};
+
+/// Filesystems whose data lives on another machine.
+const NETWORK_FS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "ncpfs", "afs", "9p", "ceph", "glusterfs", "lustre", "gpfs",
+    "davfs", "fuse.sshfs", "fuse.rclone", "fuse.glusterfs", "fuse.davfs2", "fuse.s3fs", "fuse.gcsfuse",
+];
+
+/// Kernel filesystems that hold no user files.
+const PSEUDO_FS: &[&str] = &[
+    "proc", "sysfs", "devtmpfs", "devpts", "cgroup", "cgroup2", "securityfs", "debugfs", "tracefs", "pstore",
+    "bpf", "configfs", "fusectl", "mqueue", "hugetlbfs", "autofs", "binfmt_misc", "efivarfs", "selinuxfs",
+    "nsfs", "rpc_pipefs",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    /// Per-mount options, e.g. `rw` or `noatime`.
+    pub options: Vec<String>,
+}
+
+impl MountInfo {
+    pub fn is_network(&self) -> bool {
+        NETWORK_FS.contains(&self.fs_type.as_str())
+    }
+
+    pub fn is_pseudo(&self) -> bool {
+        PSEUDO_FS.contains(&self.fs_type.as_str())
+    }
+
+    pub fn has_option(&self, option: &str) -> bool {
+        self.options.iter().any(|o| o == option)
+    }
+}
+
+/// Decodes the `\040`-style octal escapes used for spaces, tabs, newlines and
+/// backslashes in mountinfo paths.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let value = bytes[i + 1..i + 4].iter().fold(0u32, |acc, b| acc * 8 + (b - b'0') as u32);
+            out.push(value as u8);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parses the contents of `/proc/self/mountinfo`. The filesystem type follows
+/// the ` - ` separator, after a variable number of optional fields.
+pub fn parse_mountinfo(text: &str) -> Vec<MountInfo> {
+    text.lines()
+        .filter_map(|line| {
+            let (fields, rest) = line.split_once(" - ")?;
+            let mut fields = fields.split(' ').skip(4);
+            let mount_point = fields.next()?;
+            let options = fields.next()?.split(',').map(str::to_string).collect();
+            let fs_type = rest.split(' ').next()?;
+            Some(MountInfo { mount_point: PathBuf::from(unescape(mount_point)), fs_type: fs_type.to_string(), options })
+        })
+        .collect()
+}
+
+/// Mounts visible to this process; empty where mountinfo is not available.
+pub fn mounts() -> Vec<MountInfo> {
+    std::fs::read_to_string("/proc/self/mountinfo")
+        .map(|text| parse_mountinfo(&text))
+        .unwrap_or_default()
+}
+
+/// The mount holding `path`: the deepest mount point above it, and the last
+/// one mounted when several share that mount point.
+pub fn mount_of(path: &std::path::Path, mounts: &[MountInfo]) -> Option<MountInfo> {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    mounts.iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.components().count())
+        .cloned()
+}
+
+/// How directory walks treat the mounts they come across.
+#[derive(Debug, Clone, Default)]
+pub struct WalkPolicy {
+    /// Stay on the filesystem of each scanned directory.
+    pub one_file_system: bool,
+    /// Leave network and pseudo filesystems out of the walk.
+    pub skip_remote: bool,
+    mounts: HashMap<PathBuf, MountInfo>,
+}
+
+impl WalkPolicy {
+    pub fn new(one_file_system: bool, skip_remote: bool) -> WalkPolicy {
+        WalkPolicy::with_mounts(one_file_system, skip_remote, mounts())
+    }
+
+    pub fn with_mounts(one_file_system: bool, skip_remote: bool, mounts: Vec<MountInfo>) -> WalkPolicy {
+        // Later entries in mountinfo are mounted over earlier ones.
+        let mounts = mounts.into_iter().map(|m| (m.mount_point.clone(), m)).collect();
+        WalkPolicy { one_file_system, skip_remote, mounts }
+    }
+
+    /// Why the walk should not descend into `entry`, if it is a mount point
+    /// below the scanned directory that the policy leaves out.
+    fn skip_reason(&self, entry: &DirEntry) -> Option<String> {
+        if entry.depth() == 0 || !entry.file_type().is_dir() {
+            return None;
+        }
+        let mount = self.mounts.get(entry.path())?;
+        if self.skip_remote && mount.is_network() {
+            Some(format!("{} (network filesystem)", mount.fs_type))
+        } else if self.skip_remote && mount.is_pseudo() {
+            Some(format!("{} (pseudo filesystem)", mount.fs_type))
+        } else if self.one_file_system {
+            Some(format!("{} (other filesystem)", mount.fs_type))
+        } else {
+            None
+        }
+    }
+}
+
+fn active_policy() -> &'static RwLock<Option<Arc<WalkPolicy>>> {
+    static POLICY: RwLock<Option<Arc<WalkPolicy>>> = RwLock::new(None);
+    &POLICY
+}
+
+fn skipped_mounts() -> &'static Mutex<BTreeMap<PathBuf, String>> {
+    static SKIPPED: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+    &SKIPPED
+}
+
+pub fn set_walk_policy(policy: WalkPolicy) {
+    *active_policy().write().unwrap() = Some(Arc::new(policy));
+}
+
+#[cfg(unix)]
+fn device_of(entry: &DirEntry) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = if entry.depth() == 0 { std::fs::metadata(entry.path()) } else { std::fs::symlink_metadata(entry.path()) };
+    metadata.ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_entry: &DirEntry) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn root_device_of(root: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(root).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn root_device_of(_root: &Path) -> Option<u64> {
+    None
+}
+
+/// Applies the walk policy to `walker`: mount points it leaves out are not
+/// entered and are remembered for [`print_skipped_mounts`]. Network and
+/// pseudo filesystems are skipped until another policy is set.
+pub fn walk(walker: WalkDir) -> FilterEntry<IntoIter, impl FnMut(&DirEntry) -> bool> {
+    walk_filtered(walker, |_| true)
+}
+
+/// Like [`walk`], also leaving out the entries rejected by `keep`.
+pub fn walk_filtered<F: FnMut(&DirEntry) -> bool>(walker: WalkDir, keep: F) -> FilterEntry<IntoIter, impl FnMut(&DirEntry) -> bool> {
+    if active_policy().read().unwrap().is_none() {
+        set_walk_policy(WalkPolicy::new(false, true));
+    }
+    let policy = active_policy().read().unwrap().clone().unwrap_or_default();
+    walk_with_policy(walker, policy, keep)
+}
+
+/// Walks with `policy`. Staying on one filesystem is done here by comparing
+/// devices rather than with walkdir's `same_file_system`: walkdir does not
+/// enter the directories it leaves out that way, so rejecting them in
+/// `filter_entry` would skip the rest of their parent instead.
+fn walk_with_policy<F: FnMut(&DirEntry) -> bool>(walker: WalkDir, policy: Arc<WalkPolicy>, mut keep: F) -> FilterEntry<IntoIter, impl FnMut(&DirEntry) -> bool> {
+    let mut root_device = None;
+    walker.into_iter().filter_entry(move |entry| {
+        if entry.depth() == 0 {
+            root_device = device_of(entry);
+        } else if root_device.is_none() {
+            // Walks with a minimum depth never yield their root.
+            root_device = entry.path().ancestors().nth(entry.depth()).and_then(root_device_of);
+        }
+        if !keep(entry) {
+            return false;
+        }
+        let reason = policy.skip_reason(entry).or_else(|| {
+            let crosses = policy.one_file_system && entry.depth() > 0 && entry.file_type().is_dir()
+                && root_device.is_some() && device_of(entry) != root_device;
+            crosses.then(|| "other filesystem".to_string())
+        });
+        match reason {
+            Some(reason) => {
+                skipped_mounts().lock().unwrap().insert(entry.path().to_path_buf(), reason);
+                false
+            }
+            None => true,
+        }
+    })
+}
+
+/// Lists the mount points skipped by walks since the last call.
+pub fn print_skipped_mounts() {
+    let skipped = std::mem::take(&mut *skipped_mounts().lock().unwrap());
+    if skipped.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.add_row(row!["Skipped Mount Point".bold().blue(), "Filesystem".bold().blue()]);
+    for (path, reason) in &skipped {
+        table.add_row(Row::new(vec![
+            Cell::new(&path.display().to_string().blue().to_string()),
+            Cell::new(&reason.yellow().to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_mountinfo_unescapes_paths() {
+        let text = "36 35 98:0 / /mnt/my\\040disk rw,noatime master:1 - ext4 /dev/sdb1 rw\n\
+                    37 28 0:33 / /home rw,relatime shared:2 - btrfs /dev/sda2 rw\n\
+                    38 28 0:40 / /mnt/share rw,relatime - nfs4 server:/export rw\n";
+        let mounts = parse_mountinfo(text);
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(mounts[0].mount_point, PathBuf::from("/mnt/my disk"));
+        assert_eq!(mounts[1].fs_type, "btrfs");
+        assert!(mounts[2].is_network());
+        assert!(!mounts[1].is_network() && !mounts[1].is_pseudo());
+        assert!(mounts[0].has_option("noatime") && !mounts[1].has_option("noatime"));
+        let mount = mount_of(Path::new("/mnt/my disk/photos"), &mounts).unwrap();
+        assert_eq!(mount.fs_type, "ext4");
+    }
+
+    #[test]
+    fn test_skip_reason_for_mount_points() {
+        let dir = tempdir().unwrap();
+        let share = dir.path().join("share");
+        let data = dir.path().join("data");
+        std::fs::create_dir_all(share.join("inner")).unwrap();
+        std::fs::create_dir_all(&data).unwrap();
+        let mounts = vec![
+            MountInfo { mount_point: share.clone(), fs_type: "cifs".to_string(), options: Vec::new() },
+            MountInfo { mount_point: data.clone(), fs_type: "ext4".to_string(), options: Vec::new() },
+        ];
+        let reasons = |policy: &WalkPolicy| -> Vec<(PathBuf, String)> {
+            WalkDir::new(dir.path()).into_iter().flatten()
+                .filter_map(|e| policy.skip_reason(&e).map(|r| (e.path().to_path_buf(), r)))
+                .collect::<BTreeMap<_, _>>().into_iter().collect()
+        };
+
+        let default = WalkPolicy::with_mounts(false, true, mounts.clone());
+        assert_eq!(reasons(&default), vec![(share.clone(), "cifs (network filesystem)".to_string())]);
+
+        let one_fs = WalkPolicy::with_mounts(true, false, mounts);
+        assert_eq!(reasons(&one_fs), vec![
+            (data, "ext4 (other filesystem)".to_string()),
+            (share, "cifs (other filesystem)".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_one_file_system_walk_keeps_siblings_of_skipped_mounts() {
+        let dir = tempdir().unwrap();
+        for name in ["a", "b", "c", "d"] {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+            std::fs::write(dir.path().join(name).join("file"), "data").unwrap();
+        }
+        let mounts = vec![MountInfo { mount_point: dir.path().join("b"), fs_type: "ext4".to_string(), options: Vec::new() }];
+        let policy = Arc::new(WalkPolicy::with_mounts(true, true, mounts));
+
+        let mut seen: Vec<PathBuf> = walk_with_policy(WalkDir::new(dir.path()).sort_by_file_name(), policy, |_| true)
+            .flatten()
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.path().strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        seen.sort();
+        assert_eq!(seen, vec![PathBuf::from("a/file"), PathBuf::from("c/file"), PathBuf::from("d/file")]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_one_file_system_walk_of_root_sees_every_top_level_entry() {
+        // `/proc` and `/sys` are other filesystems, and everything after them
+        // must still be listed.
+        use std::os::unix::fs::MetadataExt;
+        let policy = Arc::new(WalkPolicy::with_mounts(true, false, mounts()));
+        let mount_points = policy.mounts.clone();
+        let walked: Vec<PathBuf> = walk_with_policy(WalkDir::new("/").min_depth(1).max_depth(1).sort_by_file_name(), policy, |_| true)
+            .flatten()
+            .filter(|e| e.depth() == 1)
+            .map(|e| e.into_path())
+            .collect();
+        let root = std::fs::metadata("/").unwrap().dev();
+        let mut same_device: Vec<PathBuf> = std::fs::read_dir("/").unwrap()
+            .flatten()
+            .filter(|e| e.metadata().is_ok_and(|m| !m.is_dir() || (m.dev() == root && !mount_points.contains_key(&e.path()))))
+            .map(|e| e.path())
+            .collect();
+        same_device.sort();
+        assert!(walked.len() > 1);
+        assert_eq!(walked, same_device);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SKIP_OPEN_FILES: AtomicBool = AtomicBool::new(true);
+
+/// Chooses whether files held open by other processes are left alone.
+pub fn set_skip_open_files(skip: bool) {
+    SKIP_OPEN_FILES.store(skip, Ordering::Relaxed);
+}
+
+/// A process that has a file open or mapped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// Files open in running processes, keyed by device and inode so that the
+/// same file is found whatever path it was opened under.
+#[derive(Debug, Default)]
+pub struct OpenFiles {
+    holders: HashMap<(u64, u64), Vec<Holder>>,
+}
+
+/// Parses the device (`major:minor` in hex) and inode of a `/proc/<pid>/maps`
+/// line; anonymous mappings have inode 0 and are skipped.
+#[cfg(target_os = "linux")]
+fn parse_maps_line(line: &str) -> Option<(u64, u64)> {
+    let mut fields = line.split_whitespace();
+    let device = fields.nth(3)?;
+    let inode: u64 = fields.next()?.parse().ok()?;
+    if inode == 0 {
+        return None;
+    }
+    let (major, minor) = device.split_once(':')?;
+    let major = u32::from_str_radix(major, 16).ok()?;
+    let minor = u32::from_str_radix(minor, 16).ok()?;
+    Some((libc::makedev(major, minor), inode))
+}
+
+impl OpenFiles {
+    /// Reads `/proc/*/fd` and `/proc/*/maps` of every process we may inspect.
+    /// Processes of other users are only visible when running as root.
+    #[cfg(target_os = "linux")]
+    pub fn scan() -> OpenFiles {
+        use std::os::unix::fs::MetadataExt;
+        let mut open = OpenFiles::default();
+        let own_pid = std::process::id();
+        let Ok(procs) = fs::read_dir("/proc") else { return open };
+
+        for proc_entry in procs.flatten() {
+            let Some(pid) = proc_entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else { continue };
+            if pid == own_pid {
+                continue;
+            }
+            let proc_dir = proc_entry.path();
+            let command = fs::read_to_string(proc_dir.join("comm"))
+                .map(|c| c.trim_end().to_string())
+                .unwrap_or_default();
+            let holder = Holder { pid, command };
+
+            let mut ids = Vec::new();
+            if let Ok(fds) = fs::read_dir(proc_dir.join("fd")) {
+                for fd in fds.flatten() {
+                    if let Ok(metadata) = fs::metadata(fd.path()) {
+                        if metadata.is_file() {
+                            ids.push((metadata.dev(), metadata.ino()));
+                        }
+                    }
+                }
+            }
+            if let Ok(maps) = fs::read_to_string(proc_dir.join("maps")) {
+                ids.extend(maps.lines().filter_map(parse_maps_line));
+            }
+            ids.sort_unstable();
+            ids.dedup();
+            for id in ids {
+                open.holders.entry(id).or_default().push(holder.clone());
+            }
+        }
+        open
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn scan() -> OpenFiles {
+        OpenFiles::default()
+    }
+
+    /// Scans open files unless skipping them was turned off.
+    pub fn scan_if_enabled() -> Option<OpenFiles> {
+        SKIP_OPEN_FILES.load(Ordering::Relaxed).then(OpenFiles::scan)
+    }
+
+    #[cfg(unix)]
+    pub fn holders_of(&self, path: &Path) -> Option<&[Holder]> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = fs::symlink_metadata(path).ok()?;
+        self.holders.get(&(metadata.dev(), metadata.ino())).map(|h| h.as_slice())
+    }
+
+    #[cfg(not(unix))]
+    pub fn holders_of(&self, _path: &Path) -> Option<&[Holder]> {
+        None
+    }
+}
+
+/// Describes the processes holding a file, e.g. "1234 (firefox), 99 (vim)".
+pub fn describe(holders: &[Holder]) -> String {
+    holders
+        .iter()
+        .map(|h| format!("{} ({})", h.pid, h.command))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Tests Unitaires
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_maps_line() {
+        let line = "7f1c2a000000-7f1c2a021000 r--p 00000000 fd:01 1837462    /usr/lib/libc.so.6";
+        assert_eq!(parse_maps_line(line), Some((libc::makedev(0xfd, 0x01), 1837462)));
+        assert_eq!(parse_maps_line("7ffd5000-7ffd6000 rw-p 00000000 00:00 0    [stack]"), None);
+    }
+
+    #[test]
+    fn test_scan_finds_file_open_in_child_process() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let idle = dir.path().join("idle.log");
+        fs::write(&path, "log").unwrap();
+        fs::write(&idle, "log").unwrap();
+
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .stdin(Stdio::from(fs::File::open(&path).unwrap()))
+            .spawn()
+            .unwrap();
+        // The child only takes its command name once it has executed sleep.
+        let comm = PathBuf::from(format!("/proc/{}/comm", child.id()));
+        for _ in 0..100 {
+            if fs::read_to_string(&comm).is_ok_and(|c| c.trim_end() == "sleep") {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let open = OpenFiles::scan();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let holders = open.holders_of(&path).unwrap();
+        assert!(holders.iter().any(|h| h.pid == child.id() && h.command == "sleep"));
+        assert!(open.holders_of(&idle).is_none());
+    }
+}
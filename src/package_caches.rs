@@ -0,0 +1,371 @@
+use crate::age::TimeField;
+use crate::cleaner::deletion_failure;
+use crate::mounts;
+use crate::protect;
+use crate::safe_delete;
+use crate::usage::human_size;
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A language package manager or build tool that keeps a download cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Npm,
+    Yarn,
+    Pnpm,
+    Pip,
+    Go,
+    Maven,
+    Gradle,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Npm => "npm",
+            Tool::Yarn => "Yarn",
+            Tool::Pnpm => "pnpm",
+            Tool::Pip => "pip",
+            Tool::Go => "Go",
+            Tool::Maven => "Maven",
+            Tool::Gradle => "Gradle",
+        }
+    }
+}
+
+/// How a cache splits into entries that can be pruned on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Every file stands alone, as in content-addressed stores.
+    Files,
+    /// Every directory this many levels below the root is one package.
+    Packages(usize),
+    /// Every directory holding a file with this extension is one package,
+    /// e.g. a Maven version directory with its `.pom`.
+    Marked(&'static str),
+    /// The Go module cache: its `module@version` directories, which Go makes
+    /// read-only, and the files of its download cache.
+    GoModules,
+}
+
+/// A cache directory of one tool.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub tool: Tool,
+    pub name: &'static str,
+    pub path: PathBuf,
+    layout: Layout,
+}
+
+/// A cached package or file with the last time it was used.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+/// The caches of every supported tool at their default locations, or where
+/// the tool's environment variables move them. Only existing ones are kept.
+pub fn known_caches() -> Vec<Cache> {
+    let home = dirs::home_dir();
+    let cache_dir = dirs::cache_dir();
+    let data_dir = dirs::data_local_dir();
+    let under = |base: &Option<PathBuf>, rel: &str| base.as_ref().map(|b| b.join(rel));
+    let go_path = env_path("GOPATH").or_else(|| under(&home, "go"));
+    let go_mod = env_path("GOMODCACHE").or_else(|| go_path.map(|p| p.join("pkg").join("mod")));
+    let gradle = env_path("GRADLE_USER_HOME").or_else(|| under(&home, ".gradle")).map(|p| p.join("caches"));
+
+    let candidates = [
+        (Tool::Npm, "Content cache", env_path("npm_config_cache").or_else(|| under(&home, ".npm")).map(|p| p.join("_cacache")), Layout::Files),
+        (Tool::Yarn, "Classic cache", env_path("YARN_CACHE_FOLDER").or_else(|| under(&cache_dir, "yarn")), Layout::Packages(2)),
+        (Tool::Yarn, "Berry cache", under(&home, ".yarn/berry/cache"), Layout::Files),
+        (Tool::Pnpm, "Store", under(&data_dir, "pnpm/store"), Layout::Files),
+        (Tool::Pip, "Cache", env_path("PIP_CACHE_DIR").or_else(|| under(&cache_dir, "pip")), Layout::Files),
+        (Tool::Go, "Module cache", go_mod, Layout::GoModules),
+        (Tool::Go, "Build cache", env_path("GOCACHE").or_else(|| under(&cache_dir, "go-build")), Layout::Files),
+        (Tool::Maven, "Local repository", under(&home, ".m2/repository"), Layout::Marked("pom")),
+        (Tool::Gradle, "Dependency cache", gradle.as_ref().map(|p| p.join("modules-2").join("files-2.1")), Layout::Packages(3)),
+        (Tool::Gradle, "Build cache", gradle.map(|p| p.join("build-cache-1")), Layout::Files),
+    ];
+    candidates
+        .into_iter()
+        .filter_map(|(tool, name, path, layout)| Some(Cache { tool, name, path: path?, layout }))
+        .filter(|cache| cache.path.is_dir())
+        .collect()
+}
+
+/// Size of a file or directory tree, and the latest access or modification
+/// time of the files in it. Directories are left out because listing them
+/// updates their access time.
+fn usage_of(path: &Path) -> (u64, Option<DateTime<Utc>>) {
+    let mut size = 0;
+    let mut last_used = None;
+    for entry in mounts::walk(WalkDir::new(path)).flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Ok(metadata) = entry.metadata() {
+            size += metadata.len();
+            last_used = last_used.max(TimeField::Accessed.of(&metadata)).max(TimeField::Modified.of(&metadata));
+        }
+    }
+    (size, last_used)
+}
+
+/// Whether a pnpm store file is hard-linked into some `node_modules`, in
+/// which case deleting it frees nothing.
+#[cfg(unix)]
+fn is_linked(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn is_linked(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+impl Cache {
+    pub fn size(&self) -> u64 {
+        usage_of(&self.path).0
+    }
+
+    /// The entries of this cache, files or package directories depending on
+    /// its layout.
+    fn entries(&self) -> Vec<PathBuf> {
+        let go_downloads = self.path.join("cache").join("download");
+        let mut walker = mounts::walk(WalkDir::new(&self.path).min_depth(1));
+        let mut paths = Vec::new();
+        while let Some(entry) = walker.next() {
+            let Ok(entry) = entry else { continue };
+            let is_dir = entry.file_type().is_dir();
+            let found = match self.layout {
+                Layout::Files => entry.file_type().is_file()
+                    && entry.metadata().is_ok_and(|m| self.tool != Tool::Pnpm || !is_linked(&m)),
+                Layout::Packages(depth) => entry.depth() == depth,
+                Layout::Marked(ext) => is_dir && fs::read_dir(entry.path()).is_ok_and(|mut files| {
+                    files.any(|f| f.is_ok_and(|f| f.path().extension().is_some_and(|e| e == ext)))
+                }),
+                Layout::GoModules if entry.path().starts_with(self.path.join("cache")) => {
+                    entry.file_type().is_file() && entry.path().starts_with(&go_downloads)
+                }
+                Layout::GoModules => is_dir && entry.file_name().to_string_lossy().contains('@'),
+            };
+            if found {
+                safe_delete::record_entry(&entry);
+                paths.push(entry.into_path());
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+            }
+        }
+        paths
+    }
+
+    /// The entries of this cache not used for `unused_for`.
+    pub fn stale_entries(&self, unused_for: Duration, now: DateTime<Utc>) -> Vec<CacheEntry> {
+        self.entries()
+            .into_iter()
+            .map(|path| {
+                let (size, last_used) = usage_of(&path);
+                CacheEntry { path, size, last_used }
+            })
+            .filter(|entry| entry.last_used.is_none_or(|time| now.signed_duration_since(time) > unused_for))
+            .collect()
+    }
+}
+
+pub fn print_caches_report(caches: &[Cache]) {
+    let mut table = Table::new();
+    table.add_row(row!["Tool".bold().blue(), "Cache".bold().blue(), "Path".bold().blue(), "Size".bold().blue()]);
+    let mut total = 0;
+    for cache in caches {
+        let size = cache.size();
+        total += size;
+        table.add_row(Row::new(vec![
+            Cell::new(cache.tool.name()),
+            Cell::new(cache.name),
+            Cell::new(&cache.path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(size)),
+        ]));
+    }
+    table.printstd();
+    println!("{}", format!("Total: {}", human_size(total)).green());
+}
+
+/// Prints how many entries of each cache would be pruned and their size.
+pub fn print_stale_report(stale: &[(Cache, Vec<CacheEntry>)]) {
+    let mut table = Table::new();
+    table.add_row(row!["Tool".bold().blue(), "Cache".bold().blue(), "Unused Entries".bold().blue(), "Size".bold().blue()]);
+    for (cache, entries) in stale {
+        table.add_row(Row::new(vec![
+            Cell::new(cache.tool.name()),
+            Cell::new(cache.name),
+            Cell::new(&entries.len().to_string()),
+            Cell::new(&human_size(entries.iter().map(|e| e.size).sum())),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Gives the owner write access to the directories under `path` so that the
+/// read-only trees of the Go module cache can be deleted. Protected paths
+/// and a `path` that is a symlink are refused before anything is changed.
+#[cfg(unix)]
+fn make_writable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    protect::check(path)?;
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+    }
+    for entry in WalkDir::new(path).into_iter().flatten().filter(|e| e.file_type().is_dir()) {
+        if let Ok(metadata) = entry.metadata() {
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(permissions.mode() | 0o700);
+            let _ = fs::set_permissions(entry.path(), permissions);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_writable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Deletes the stale entries of each cache and returns the space freed.
+/// Results are summarized per cache since stores hold many small files.
+pub fn prune(stale: &[(Cache, Vec<CacheEntry>)]) -> u64 {
+    let mut table = Table::new();
+    table.add_row(row!["Tool".bold().blue(), "Cache".bold().blue(), "Deleted".bold().blue(), "Failed".bold().blue(), "Space Freed".bold().blue()]);
+    let mut freed = 0;
+
+    for (cache, entries) in stale {
+        let (mut deleted, mut failed, mut cache_freed) = (0, 0, 0);
+        for entry in entries {
+            let result = if fs::symlink_metadata(&entry.path).is_ok_and(|m| m.is_dir()) {
+                if cache.layout == Layout::GoModules {
+                    make_writable(&entry.path).and_then(|_| safe_delete::remove_dir_all(&entry.path))
+                } else {
+                    safe_delete::remove_dir_all(&entry.path)
+                }
+            } else {
+                safe_delete::remove_file(&entry.path)
+            };
+            match result {
+                Ok(_) => {
+                    deleted += 1;
+                    cache_freed += entry.size;
+                }
+                Err(e) => {
+                    deletion_failure(&entry.path, &e);
+                    failed += 1;
+                }
+            }
+        }
+        freed += cache_freed;
+        table.add_row(Row::new(vec![
+            Cell::new(cache.tool.name()),
+            Cell::new(cache.name),
+            Cell::new(&deleted.to_string().green().to_string()),
+            Cell::new(&failed.to_string()),
+            Cell::new(&human_size(cache_freed)),
+        ]));
+    }
+
+    table.printstd();
+    println!("{}", format!("Space freed: {}", human_size(freed)).green());
+    freed
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    fn age(path: &Path, days: u64) {
+        let time = FileTime::from_system_time(SystemTime::now() - std::time::Duration::from_secs(days * 86400));
+        filetime::set_file_times(path, time, time).unwrap();
+    }
+
+    #[test]
+    fn test_stale_entries_by_layout() {
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+
+        let maven = dir.path().join("m2");
+        for version in ["1.0", "2.0"] {
+            let path = maven.join("org").join("lib").join(version);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join(format!("lib-{}.pom", version)), "<project/>").unwrap();
+            fs::write(path.join(format!("lib-{}.jar", version)), vec![0u8; 100]).unwrap();
+        }
+        for file in ["lib-1.0.pom", "lib-1.0.jar"] {
+            age(&maven.join("org").join("lib").join("1.0").join(file), 200);
+        }
+        let cache = Cache { tool: Tool::Maven, name: "Local repository", path: maven.clone(), layout: Layout::Marked("pom") };
+        let stale = cache.stale_entries(Duration::days(90), now);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].path, maven.join("org").join("lib").join("1.0"));
+        assert_eq!(stale[0].size, 110);
+
+        let pip = dir.path().join("pip");
+        fs::create_dir_all(pip.join("http").join("a")).unwrap();
+        fs::write(pip.join("http").join("a").join("old"), "old").unwrap();
+        fs::write(pip.join("http").join("a").join("new"), "new").unwrap();
+        age(&pip.join("http").join("a").join("old"), 100);
+        let cache = Cache { tool: Tool::Pip, name: "Cache", path: pip.clone(), layout: Layout::Files };
+        let stale = cache.stale_entries(Duration::days(90), now);
+        assert_eq!(stale.iter().map(|e| &e.path).collect::<Vec<_>>(), vec![&pip.join("http").join("a").join("old")]);
+        assert_eq!(prune(&[(cache, stale)]), 3);
+        assert!(pip.join("http").join("a").join("new").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prune_read_only_go_modules() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let module = dir.path().join("github.com").join("user").join("mod@v1.2.0");
+        fs::create_dir_all(module.join("sub")).unwrap();
+        let download = dir.path().join("cache").join("download").join("github.com").join("user").join("mod").join("@v");
+        fs::create_dir_all(&download).unwrap();
+        fs::write(module.join("sub").join("a.go"), vec![0u8; 40]).unwrap();
+        fs::write(download.join("v1.2.0.zip"), vec![0u8; 30]).unwrap();
+        fs::write(download.join("v1.3.0.zip"), vec![0u8; 30]).unwrap();
+        for path in [module.join("sub").join("a.go"), download.join("v1.2.0.zip")] {
+            age(&path, 365);
+        }
+        for path in [module.join("sub"), module.clone()] {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o555)).unwrap();
+        }
+
+        let cache = Cache { tool: Tool::Go, name: "Module cache", path: dir.path().to_path_buf(), layout: Layout::GoModules };
+        let stale = cache.stale_entries(Duration::days(30), Utc::now());
+        assert_eq!(stale.iter().map(|e| &e.path).collect::<Vec<_>>(), vec![&download.join("v1.2.0.zip"), &module]);
+        assert_eq!(prune(&[(cache, stale)]), 70);
+        assert!(!module.exists() && download.join("v1.3.0.zip").exists());
+
+        // A module swapped for a symlink is not made writable through it.
+        let outside = tempdir().unwrap();
+        fs::set_permissions(outside.path(), fs::Permissions::from_mode(0o555)).unwrap();
+        std::os::unix::fs::symlink(outside.path(), &module).unwrap();
+        assert!(make_writable(&module).is_err());
+        assert_eq!(fs::metadata(outside.path()).unwrap().permissions().mode() & 0o777, 0o555);
+        fs::set_permissions(outside.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
@@ -0,0 +1,408 @@
+use crate::age::parse_duration;
+use crate::tui::centered;
+use crate::usage::human_size;
+use chrono::{DateTime, Duration, Utc};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Age,
+}
+
+impl SortKey {
+    fn next(self) -> SortKey {
+        match self {
+            SortKey::Name => SortKey::Size,
+            SortKey::Size => SortKey::Age,
+            SortKey::Age => SortKey::Name,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Age => "age",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Directory,
+    Extension,
+}
+
+impl GroupBy {
+    fn name(self) -> &'static str {
+        match self {
+            GroupBy::Directory => "directory",
+            GroupBy::Extension => "extension",
+        }
+    }
+}
+
+/// A line of the list: a group heading or one candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Row {
+    Group(String),
+    Item(usize),
+}
+
+/// Scan results the user picks from before a single deletion.
+#[derive(Debug)]
+pub struct Picker {
+    candidates: Vec<Candidate>,
+    /// Case-insensitive text the listed paths must contain.
+    pub filter: String,
+    pub sort: SortKey,
+    pub group_by: GroupBy,
+}
+
+impl Picker {
+    pub fn new(paths: Vec<PathBuf>) -> Picker {
+        let candidates = paths
+            .into_iter()
+            .map(|path| {
+                let metadata = std::fs::symlink_metadata(&path).ok();
+                Candidate {
+                    size: metadata.as_ref().map_or(0, |m| m.len()),
+                    modified: metadata.and_then(|m| m.modified().ok()).map(DateTime::<Utc>::from),
+                    path,
+                    selected: false,
+                }
+            })
+            .collect();
+        Picker { candidates, filter: String::new(), sort: SortKey::Name, group_by: GroupBy::Directory }
+    }
+
+    pub fn candidate(&self, index: usize) -> &Candidate {
+        &self.candidates[index]
+    }
+
+    fn group_of(&self, index: usize) -> String {
+        let path = &self.candidates[index].path;
+        match self.group_by {
+            GroupBy::Directory => path.parent().map(|p| p.display().to_string()).unwrap_or_default(),
+            GroupBy::Extension => match path.extension() {
+                Some(ext) => format!(".{}", ext.to_string_lossy().to_ascii_lowercase()),
+                None => "(no extension)".to_string(),
+            },
+        }
+    }
+
+    fn visible(&self) -> impl Iterator<Item = usize> + '_ {
+        let filter = self.filter.to_lowercase();
+        (0..self.candidates.len())
+            .filter(move |&i| self.candidates[i].path.to_string_lossy().to_lowercase().contains(&filter))
+    }
+
+    /// The visible candidates by group, each group sorted by the sort key.
+    fn groups(&self) -> BTreeMap<String, Vec<usize>> {
+        let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for index in self.visible() {
+            groups.entry(self.group_of(index)).or_default().push(index);
+        }
+        for members in groups.values_mut() {
+            let c = &self.candidates;
+            match self.sort {
+                SortKey::Name => members.sort_by(|&a, &b| c[a].path.cmp(&c[b].path)),
+                SortKey::Size => members.sort_by(|&a, &b| c[b].size.cmp(&c[a].size).then_with(|| c[a].path.cmp(&c[b].path))),
+                SortKey::Age => members.sort_by(|&a, &b| c[a].modified.cmp(&c[b].modified).then_with(|| c[a].path.cmp(&c[b].path))),
+            }
+        }
+        groups
+    }
+
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (group, members) in self.groups() {
+            rows.push(Row::Group(group));
+            rows.extend(members.into_iter().map(Row::Item));
+        }
+        rows
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        self.candidates[index].selected = !self.candidates[index].selected;
+    }
+
+    /// Selects every visible candidate of `group`, or clears them when they
+    /// are all selected already.
+    pub fn toggle_group(&mut self, group: &str) {
+        let members = self.groups().remove(group).unwrap_or_default();
+        let select = !members.iter().all(|&i| self.candidates[i].selected);
+        for index in members {
+            self.candidates[index].selected = select;
+        }
+    }
+
+    /// Inverts the selection of the visible candidates.
+    pub fn invert(&mut self) {
+        for index in self.visible().collect::<Vec<_>>() {
+            self.toggle(index);
+        }
+    }
+
+    /// Selects the visible candidates last modified more than `age` before
+    /// `now`.
+    pub fn select_older_than(&mut self, age: Duration, now: DateTime<Utc>) {
+        for index in self.visible().collect::<Vec<_>>() {
+            let candidate = &mut self.candidates[index];
+            if candidate.modified.is_some_and(|m| now.signed_duration_since(m) > age) {
+                candidate.selected = true;
+            }
+        }
+    }
+
+    /// Selected paths, hidden ones included, with their total size.
+    pub fn selected(&self) -> (Vec<PathBuf>, u64) {
+        let selected = self.candidates.iter().filter(|c| c.selected);
+        (selected.clone().map(|c| c.path.clone()).collect(), selected.map(|c| c.size).sum())
+    }
+}
+
+enum Mode {
+    Browse,
+    Filter,
+    /// Typing the age, e.g. "30d" or "6mo".
+    OlderThan(String),
+    Confirm,
+}
+
+struct App {
+    picker: Picker,
+    state: ListState,
+    mode: Mode,
+}
+
+impl App {
+    fn current(&self) -> Option<Row> {
+        self.picker.rows().get(self.state.selected()?).cloned()
+    }
+
+    /// Toggles the group under the cursor, or the group of the candidate
+    /// under the cursor.
+    fn toggle_current_group(&mut self) {
+        let group = match self.current() {
+            Some(Row::Group(group)) => group,
+            Some(Row::Item(index)) => self.picker.group_of(index),
+            None => return,
+        };
+        self.picker.toggle_group(&group);
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, footer] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let filter = match &app.mode {
+        Mode::Filter => format!("{}_", app.picker.filter),
+        _ => app.picker.filter.clone(),
+    };
+    frame.render_widget(
+        Line::from(format!(
+            " Filter: {}  | grouped by {}, sorted by {}",
+            filter,
+            app.picker.group_by.name(),
+            app.picker.sort.name()
+        )).style(Style::new().bold().reversed()),
+        header,
+    );
+
+    let items: Vec<ListItem> = app.picker.rows()
+        .into_iter()
+        .map(|row| match row {
+            Row::Group(group) => ListItem::new(Line::from(Span::styled(group, Style::new().bold().fg(Color::Blue)))),
+            Row::Item(index) => {
+                let candidate = app.picker.candidate(index);
+                let name = candidate.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                let name = match app.picker.group_by {
+                    GroupBy::Directory => name,
+                    GroupBy::Extension => candidate.path.display().to_string(),
+                };
+                let style = if candidate.selected { Style::new().fg(Color::Green) } else { Style::new() };
+                ListItem::new(Line::from(vec![
+                    Span::raw(if candidate.selected { "  [x] " } else { "  [ ] " }),
+                    Span::raw(format!("{:>10}  ", human_size(candidate.size))),
+                    Span::raw(format!("{}  ", candidate.modified.map(|m| m.format("%Y-%m-%d").to_string()).unwrap_or_default())),
+                    Span::styled(name, style),
+                ]))
+            }
+        })
+        .collect();
+    frame.render_stateful_widget(List::new(items).highlight_style(Style::new().reversed()), body, &mut app.state);
+
+    let (selected, bytes) = app.picker.selected();
+    frame.render_widget(
+        Line::from(format!(
+            " Selected: {} ({}) | space select  a folder/group  i invert  n older than  / filter  o sort  g group  enter delete  q quit",
+            selected.len(),
+            human_size(bytes)
+        )).style(Style::new().reversed()),
+        footer,
+    );
+
+    let popup = match &app.mode {
+        Mode::OlderThan(age) => Some((" Select older than ", vec![
+            Line::from(format!("Age (e.g. 30d, 2w, 6mo): {}_", age)),
+            Line::from(""),
+            Line::from("enter = select, esc = cancel"),
+        ])),
+        Mode::Confirm => Some((" Confirm ", vec![
+            Line::from(format!("Delete {} selected files ({})?", selected.len(), human_size(bytes))),
+            Line::from(""),
+            Line::from("y = confirm, any other key = cancel"),
+        ])),
+        _ => None,
+    };
+    if let Some((title, text)) = popup {
+        let area = centered(frame.area(), 60, 5);
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(text).block(Block::bordered().title(title)), area);
+    }
+}
+
+fn run_app(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<bool> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let count = app.picker.rows().len();
+        match &mut app.mode {
+            Mode::Confirm => {
+                if key.code == KeyCode::Char('y') {
+                    return Ok(true);
+                }
+                app.mode = Mode::Browse;
+            }
+            Mode::Filter => match key.code {
+                KeyCode::Enter | KeyCode::Esc => app.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    app.picker.filter.pop();
+                }
+                KeyCode::Char(c) => app.picker.filter.push(c),
+                _ => {}
+            },
+            Mode::OlderThan(age) => match key.code {
+                KeyCode::Enter => {
+                    if let Ok(age) = parse_duration(age) {
+                        app.picker.select_older_than(age, Utc::now());
+                    }
+                    app.mode = Mode::Browse;
+                }
+                KeyCode::Esc => app.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    age.pop();
+                }
+                KeyCode::Char(c) if c.is_ascii_alphanumeric() => age.push(c),
+                _ => {}
+            },
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+                    app.state.select(Some((app.state.selected().unwrap_or(0) + 1).min(count - 1)));
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.state.select(Some(app.state.selected().unwrap_or(0).saturating_sub(1)));
+                }
+                KeyCode::Char(' ') => match app.current() {
+                    Some(Row::Item(index)) => app.picker.toggle(index),
+                    Some(Row::Group(_)) => app.toggle_current_group(),
+                    None => {}
+                },
+                KeyCode::Char('a') => app.toggle_current_group(),
+                KeyCode::Char('i') => app.picker.invert(),
+                KeyCode::Char('n') => app.mode = Mode::OlderThan(String::new()),
+                KeyCode::Char('/') => app.mode = Mode::Filter,
+                KeyCode::Char('o') => app.picker.sort = app.picker.sort.next(),
+                KeyCode::Char('g') => {
+                    app.picker.group_by = match app.picker.group_by {
+                        GroupBy::Directory => GroupBy::Extension,
+                        GroupBy::Extension => GroupBy::Directory,
+                    };
+                }
+                KeyCode::Enter if !app.picker.selected().0.is_empty() => app.mode = Mode::Confirm,
+                _ => {}
+            },
+        }
+        let count = app.picker.rows().len();
+        if app.state.selected().is_some_and(|s| s >= count) {
+            app.state.select(Some(count.saturating_sub(1)));
+        }
+    }
+}
+
+/// Lets the user pick from `files` and returns the confirmed selection, or
+/// `None` when the user quits.
+pub fn pick(files: Vec<PathBuf>) -> io::Result<Option<Vec<PathBuf>>> {
+    let mut app = App { picker: Picker::new(files), state: ListState::default().with_selected(Some(0)), mode: Mode::Browse };
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, &mut app);
+    ratatui::restore();
+    Ok(result?.then(|| app.picker.selected().0))
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_groups_and_bulk_selection() {
+        let dir = tempdir().unwrap();
+        let logs = dir.path().join("logs");
+        fs::create_dir_all(&logs).unwrap();
+        let old = logs.join("old.log");
+        let new = logs.join("new.log");
+        let tmp = dir.path().join("a.tmp");
+        for (path, size) in [(&old, 10), (&new, 300), (&tmp, 20)] {
+            fs::write(path, vec![0u8; size]).unwrap();
+        }
+        let month_ago = SystemTime::now() - std::time::Duration::from_secs(30 * 86400);
+        fs::File::options().write(true).open(&old).unwrap().set_modified(month_ago).unwrap();
+
+        let mut picker = Picker::new(vec![old.clone(), new.clone(), tmp.clone()]);
+        picker.sort = SortKey::Size;
+        let rows = picker.rows();
+        let logs_group = logs.display().to_string();
+        assert_eq!(rows[2], Row::Group(logs_group.clone()));
+        assert_eq!(rows[3..], [Row::Item(1), Row::Item(0)]);
+
+        picker.select_older_than(Duration::days(7), Utc::now());
+        assert_eq!(picker.selected(), (vec![old.clone()], 10));
+
+        picker.toggle_group(&logs_group);
+        assert_eq!(picker.selected().0, vec![old.clone(), new.clone()]);
+        picker.invert();
+        assert_eq!(picker.selected(), (vec![tmp.clone()], 20));
+
+        picker.group_by = GroupBy::Extension;
+        picker.filter = "LOG".to_string();
+        assert_eq!(picker.rows(), vec![Row::Group(".log".to_string()), Row::Item(1), Row::Item(0)]);
+        picker.invert();
+        assert_eq!(picker.selected().0, vec![old, new, tmp]);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::cleaner::{deletion_failure, has_permission, is_permission_denied};
+use crate::mounts;
+use crate::safe_delete;
+use crate::usage::human_size;
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A build system recognized by the marker file at the root of a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectKind {
+    Cargo,
+    Node,
+    Python,
+    Gradle,
+    CMake,
+}
+
+impl ProjectKind {
+    const ALL: [ProjectKind; 5] = [ProjectKind::Cargo, ProjectKind::Node, ProjectKind::Python, ProjectKind::Gradle, ProjectKind::CMake];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProjectKind::Cargo => "Rust",
+            ProjectKind::Node => "Node.js",
+            ProjectKind::Python => "Python",
+            ProjectKind::Gradle => "Gradle",
+            ProjectKind::CMake => "CMake",
+        }
+    }
+
+    fn markers(&self) -> &'static [&'static str] {
+        match self {
+            ProjectKind::Cargo => &["Cargo.toml"],
+            ProjectKind::Node => &["package.json"],
+            ProjectKind::Python => &["pyproject.toml"],
+            ProjectKind::Gradle => &["build.gradle", "build.gradle.kts"],
+            ProjectKind::CMake => &["CMakeLists.txt"],
+        }
+    }
+
+    /// Build output directories found next to the marker file.
+    fn artifact_dirs(&self) -> &'static [&'static str] {
+        match self {
+            ProjectKind::Cargo => &["target"],
+            ProjectKind::Node => &["node_modules", "dist"],
+            ProjectKind::Python => &["dist"],
+            ProjectKind::Gradle => &[".gradle", "build"],
+            ProjectKind::CMake => &["build"],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub kinds: Vec<ProjectKind>,
+    pub artifacts: Vec<Artifact>,
+    /// Newest modification time of the files outside the build artifacts.
+    pub last_activity: Option<DateTime<Utc>>,
+}
+
+impl Project {
+    pub fn artifact_size(&self) -> u64 {
+        self.artifacts.iter().map(|a| a.size).sum()
+    }
+
+    /// Whether nothing outside the build artifacts changed for `inactive_for`.
+    pub fn is_stale(&self, inactive_for: Duration, now: DateTime<Utc>) -> bool {
+        self.last_activity.is_none_or(|time| now.signed_duration_since(time) > inactive_for)
+    }
+
+    fn is_artifact(&self, dir: &Path) -> bool {
+        let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if self.kinds.contains(&ProjectKind::Python) && name == "__pycache__" {
+            return true;
+        }
+        dir.parent() == Some(self.root.as_path()) && self.kinds.iter().any(|k| k.artifact_dirs().contains(&name))
+    }
+}
+
+fn directory_size(dir: &Path) -> u64 {
+    mounts::walk(WalkDir::new(dir))
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Finds the projects under `dirs_to_scan` that hold build artifacts. The
+/// contents of artifact directories and `.git` are not searched, so packages
+/// inside `node_modules` are not taken for projects.
+pub fn find_projects<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>) -> Vec<Project> {
+    let mut projects: Vec<Project> = Vec::new();
+
+    for dir in dirs_to_scan {
+        let dir = dir.as_ref();
+        if exclude_dirs.contains(dir.to_str().unwrap_or_default()) || !has_permission(dir) {
+            continue;
+        }
+        // Nearest enclosing project of every directory seen so far, and of
+        // every project.
+        let mut parent_project: HashMap<usize, usize> = HashMap::new();
+        let mut project_of: HashMap<PathBuf, Option<usize>> = HashMap::new();
+        let mut walker = mounts::walk_filtered(WalkDir::new(dir), |e| {
+            !exclude_dirs.contains(e.path().to_str().unwrap_or_default()) && e.file_name() != ".git"
+        });
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    if is_permission_denied(&e) {
+                        eprintln!("{}", format!("Permission denied: {}", e).red());
+                    } else {
+                        eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                    }
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let enclosing = path.parent().and_then(|p| project_of.get(p)).copied().flatten();
+
+            if !entry.file_type().is_dir() {
+                // A change in a workspace member is activity in the
+                // workspace, and in every project around it.
+                if let (Some(index), Ok(metadata)) = (enclosing, entry.metadata()) {
+                    let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+                    let mut project = Some(index);
+                    while let Some(index) = project {
+                        projects[index].last_activity = projects[index].last_activity.max(modified);
+                        project = parent_project.get(&index).copied();
+                    }
+                }
+                continue;
+            }
+
+            if let Some(index) = enclosing.filter(|&i| projects[i].is_artifact(path)) {
+                safe_delete::record_entry(&entry);
+                projects[index].artifacts.push(Artifact { path: path.to_path_buf(), size: directory_size(path) });
+                walker.skip_current_dir();
+                continue;
+            }
+
+            let kinds: Vec<ProjectKind> = ProjectKind::ALL
+                .into_iter()
+                .filter(|kind| kind.markers().iter().any(|marker| path.join(marker).is_file()))
+                .collect();
+            if kinds.is_empty() {
+                project_of.insert(path.to_path_buf(), enclosing);
+            } else {
+                projects.push(Project { root: path.to_path_buf(), kinds, artifacts: Vec::new(), last_activity: None });
+                if let Some(parent) = enclosing {
+                    parent_project.insert(projects.len() - 1, parent);
+                }
+                project_of.insert(path.to_path_buf(), Some(projects.len() - 1));
+            }
+        }
+    }
+
+    projects.retain(|p| !p.artifacts.is_empty());
+    projects.sort_by(|a, b| b.artifact_size().cmp(&a.artifact_size()).then_with(|| a.root.cmp(&b.root)));
+    projects
+}
+
+pub fn print_projects_report(projects: &[Project], inactive_for: Duration, now: DateTime<Utc>) {
+    let mut table = Table::new();
+    table.add_row(row!["Project".bold().blue(), "Type".bold().blue(), "Artifacts".bold().blue(), "Size".bold().blue(), "Last Activity".bold().blue(), "Status".bold().blue()]);
+    for project in projects {
+        let artifacts: Vec<String> = project.artifacts.iter()
+            .map(|a| a.path.strip_prefix(&project.root).unwrap_or(&a.path).display().to_string())
+            .collect();
+        let status = if project.is_stale(inactive_for, now) { "To clean".yellow() } else { "Active".green() };
+        table.add_row(Row::new(vec![
+            Cell::new(&project.root.display().to_string().blue().to_string()),
+            Cell::new(&project.kinds.iter().map(|k| k.name()).collect::<Vec<_>>().join(", ")),
+            Cell::new(&artifacts.join("\n")),
+            Cell::new(&human_size(project.artifact_size())),
+            Cell::new(&project.last_activity.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default()),
+            Cell::new(&status.to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Deletes the artifact directories of `projects` and returns the space freed.
+pub fn remove_artifacts(projects: &[Project]) -> u64 {
+    let mut table = Table::new();
+    table.add_row(row!["Directory".bold().blue(), "Status".bold().blue()]);
+    let mut freed = 0;
+
+    for artifact in projects.iter().flat_map(|p| &p.artifacts) {
+        let status = match safe_delete::remove_dir_all(&artifact.path) {
+            Ok(_) => {
+                freed += artifact.size;
+                "Deleted".to_string()
+            }
+            Err(e) => deletion_failure(&artifact.path, &e),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&artifact.path.display().to_string().blue().to_string()),
+            Cell::new(&status.green().to_string()),
+        ]));
+    }
+
+    table.printstd();
+    println!("{}", format!("Space freed: {}", human_size(freed)).green());
+    freed
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_projects_and_remove_stale_artifacts() {
+        let dir = tempdir().unwrap();
+        let rust = dir.path().join("old-rust");
+        fs::create_dir_all(rust.join("src")).unwrap();
+        fs::create_dir_all(rust.join("target").join("debug")).unwrap();
+        fs::write(rust.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(rust.join("src").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(rust.join("target").join("debug").join("app"), vec![0u8; 1000]).unwrap();
+        let year_ago = filetime::FileTime::from_system_time(SystemTime::now() - std::time::Duration::from_secs(365 * 86400));
+        for file in [rust.join("Cargo.toml"), rust.join("src").join("main.rs")] {
+            filetime::set_file_mtime(file, year_ago).unwrap();
+        }
+
+        let web = dir.path().join("web");
+        let package = web.join("node_modules").join("left-pad");
+        fs::create_dir_all(&package).unwrap();
+        fs::create_dir_all(web.join("api").join("__pycache__")).unwrap();
+        fs::write(web.join("package.json"), "{}").unwrap();
+        fs::write(package.join("package.json"), "{}").unwrap();
+        fs::write(package.join("index.js"), vec![0u8; 300]).unwrap();
+        fs::write(web.join("api").join("__pycache__").join("a.pyc"), vec![0u8; 50]).unwrap();
+
+        // Not part of any project, so never an artifact.
+        fs::create_dir_all(dir.path().join("notes").join("build")).unwrap();
+
+        let projects = find_projects(&[dir.path()], &HashSet::new());
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].root, rust);
+        assert_eq!(projects[0].kinds, vec![ProjectKind::Cargo]);
+        assert_eq!(projects[0].artifact_size(), 1000);
+        assert_eq!(projects[1].root, web);
+        // __pycache__ only counts in Python projects.
+        assert_eq!(projects[1].artifacts.len(), 1);
+        assert_eq!(projects[1].artifact_size(), 302);
+
+        let now = Utc::now();
+        let stale: Vec<Project> = projects.into_iter().filter(|p| p.is_stale(Duration::days(30), now)).collect();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(remove_artifacts(&stale), 1000);
+        assert!(!rust.join("target").exists());
+        assert!(rust.join("src").join("main.rs").exists());
+        assert!(web.join("node_modules").exists());
+    }
+
+    #[test]
+    fn test_member_activity_keeps_workspace_active() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        let member = workspace.join("crates").join("core");
+        fs::create_dir_all(workspace.join("target")).unwrap();
+        fs::create_dir_all(member.join("src")).unwrap();
+        fs::write(workspace.join("Cargo.toml"), "[workspace]").unwrap();
+        fs::write(workspace.join("target").join("lib.rlib"), vec![0u8; 100]).unwrap();
+        fs::write(member.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(member.join("src").join("lib.rs"), "").unwrap();
+        let year_ago = filetime::FileTime::from_system_time(SystemTime::now() - std::time::Duration::from_secs(365 * 86400));
+        filetime::set_file_mtime(workspace.join("Cargo.toml"), year_ago).unwrap();
+
+        // A Node project's build directory may hold sources.
+        let web = dir.path().join("web");
+        fs::create_dir_all(web.join("build")).unwrap();
+        fs::create_dir_all(web.join("node_modules")).unwrap();
+        fs::write(web.join("package.json"), "{}").unwrap();
+        fs::write(web.join("build").join("deploy.sh"), "").unwrap();
+
+        let projects = find_projects(&[dir.path()], &HashSet::new());
+        let root = projects.iter().find(|p| p.root == workspace).unwrap();
+        assert!(!root.is_stale(Duration::days(30), Utc::now()));
+        let web = projects.iter().find(|p| p.root == web).unwrap();
+        assert_eq!(web.artifacts.iter().map(|a| a.path.file_name().unwrap()).collect::<Vec<_>>(), vec!["node_modules"]);
+    }
+}
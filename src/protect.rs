@@ -0,0 +1,242 @@
+use crate::config::ProtectConfig;
+use crate::mounts;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+/// Whether a rule covers only the path itself or everything below it too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Exact,
+    Tree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub path: PathBuf,
+    pub scope: Scope,
+    /// Names the rule in error messages, e.g. "system directory".
+    pub kind: String,
+}
+
+impl Rule {
+    fn new(path: impl Into<PathBuf>, scope: Scope, kind: &str) -> Rule {
+        let path = path.into();
+        // Compare against where the rule really points, so that /lib is still
+        // protected when it is a link to /usr/lib.
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+        Rule { path, scope, kind: kind.to_string() }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match self.scope {
+            Scope::Exact => path == self.path,
+            Scope::Tree => path.starts_with(&self.path),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind, self.path.display())
+    }
+}
+
+/// Error returned when a deletion targets a protected path.
+#[derive(Debug, Clone)]
+pub struct Protected {
+    pub path: PathBuf,
+    pub rule: Rule,
+}
+
+impl fmt::Display for Protected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is protected by rule: {}", self.path.display(), self.rule)
+    }
+}
+
+impl std::error::Error for Protected {}
+
+pub fn is_protected(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|e| e.is::<Protected>())
+}
+
+/// Paths that must never be deleted. User deny rules win over user allow
+/// rules, which win over the built-in rules.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    builtin: Vec<Rule>,
+    deny: Vec<Rule>,
+    allow: Vec<Rule>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn system_rules() -> Vec<Rule> {
+    let mut rules: Vec<Rule> = ["/bin", "/boot", "/dev", "/etc", "/lib", "/lib32", "/lib64", "/libx32", "/proc", "/run", "/sbin", "/sys", "/usr", "/var/lib"]
+        .iter()
+        .map(|p| Rule::new(p, Scope::Tree, "system directory"))
+        .collect();
+    rules.extend(["/", "/home", "/root", "/var", "/opt", "/srv", "/mnt", "/media", "/tmp", "/var/tmp"]
+        .iter()
+        .map(|p| Rule::new(p, Scope::Exact, "system directory")));
+    rules
+}
+
+#[cfg(target_os = "macos")]
+fn system_rules() -> Vec<Rule> {
+    let mut rules: Vec<Rule> = ["/System", "/Library", "/Applications", "/bin", "/sbin", "/usr", "/private/etc", "/private/var/db"]
+        .iter()
+        .map(|p| Rule::new(p, Scope::Tree, "system directory"))
+        .collect();
+    rules.extend(["/", "/Users", "/Volumes", "/private", "/private/var", "/tmp"]
+        .iter()
+        .map(|p| Rule::new(p, Scope::Exact, "system directory")));
+    rules
+}
+
+#[cfg(windows)]
+fn system_rules() -> Vec<Rule> {
+    let mut rules: Vec<Rule> = [r"C:\Windows", r"C:\Program Files", r"C:\Program Files (x86)", r"C:\ProgramData"]
+        .iter()
+        .map(|p| Rule::new(p, Scope::Tree, "system directory"))
+        .collect();
+    rules.extend([r"C:\", r"C:\Users"].iter().map(|p| Rule::new(p, Scope::Exact, "system directory")));
+    rules
+}
+
+/// Removes `.` and `..` without touching the filesystem.
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Resolves `path` the way a deletion would reach it: the directories leading
+/// to it are resolved, but a final symlink is the link itself.
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let absolute = lexical_normalize(&absolute);
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => std::fs::canonicalize(parent).unwrap_or_else(|_| parent.to_path_buf()).join(name),
+        _ => absolute,
+    }
+}
+
+impl Policy {
+    /// The built-in rules: system directories, the home directory itself, the
+    /// XDG user directories other than Downloads and every mount point.
+    pub fn builtin() -> Policy {
+        let mut builtin = system_rules();
+        if let Some(home) = dirs::home_dir() {
+            builtin.push(Rule::new(home, Scope::Exact, "home directory"));
+        }
+        let user_dirs = [
+            dirs::desktop_dir(),
+            dirs::document_dir(),
+            dirs::audio_dir(),
+            dirs::picture_dir(),
+            dirs::public_dir(),
+            dirs::template_dir(),
+            dirs::video_dir(),
+        ];
+        let downloads = dirs::download_dir();
+        for dir in user_dirs.into_iter().flatten() {
+            if Some(&dir) != downloads.as_ref() {
+                builtin.push(Rule::new(dir, Scope::Exact, "user directory"));
+            }
+        }
+        for mount in mounts::mounts() {
+            builtin.push(Rule::new(mount.mount_point, Scope::Exact, "mount point"));
+        }
+        Policy { builtin, ..Default::default() }
+    }
+
+    pub fn with_config(mut self, config: &ProtectConfig) -> Policy {
+        self.deny.extend(config.deny.iter().map(|p| Rule::new(p, Scope::Tree, "deny list entry")));
+        self.allow.extend(config.allow.iter().map(|p| Rule::new(p, Scope::Tree, "allow list entry")));
+        self
+    }
+
+    /// Returns the rule that forbids deleting `path`, if any.
+    pub fn blocking_rule(&self, path: &Path) -> Option<&Rule> {
+        let path = resolve(path);
+        if let Some(rule) = self.deny.iter().find(|r| r.matches(&path)) {
+            return Some(rule);
+        }
+        if self.allow.iter().any(|r| r.matches(&path)) {
+            return None;
+        }
+        self.builtin.iter().find(|r| r.matches(&path))
+    }
+}
+
+fn active_policy() -> &'static RwLock<Option<Policy>> {
+    static POLICY: RwLock<Option<Policy>> = RwLock::new(None);
+    &POLICY
+}
+
+/// Replaces the policy enforced by every deleting function.
+pub fn set_policy(policy: Policy) {
+    *active_policy().write().unwrap() = Some(policy);
+}
+
+/// Fails with a [`Protected`] error if `path` must not be deleted. The
+/// built-in policy applies until another one is set.
+pub fn check(path: &Path) -> io::Result<()> {
+    if active_policy().read().unwrap().is_none() {
+        set_policy(Policy::builtin());
+    }
+    let guard = active_policy().read().unwrap();
+    match guard.as_ref().and_then(|policy| policy.blocking_rule(path)) {
+        Some(rule) => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            Protected { path: path.to_path_buf(), rule: rule.clone() },
+        )),
+        None => Ok(()),
+    }
+}
+
+// Tests Unitaires
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_builtin_rules_block_system_and_home() {
+        let policy = Policy::builtin();
+        assert_eq!(policy.blocking_rule(Path::new("/")).unwrap().kind, "system directory");
+        assert!(policy.blocking_rule(Path::new("/etc/passwd")).is_some());
+        assert!(policy.blocking_rule(Path::new("/usr/../etc")).is_some());
+        assert!(policy.blocking_rule(Path::new("/home")).is_some());
+        if let Some(home) = dirs::home_dir() {
+            assert!(policy.blocking_rule(&home).is_some());
+        }
+    }
+
+    #[test]
+    fn test_config_deny_and_allow() {
+        let dir = tempdir().unwrap();
+        let keep = dir.path().join("keep");
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(&keep).unwrap();
+        std::fs::create_dir_all(&cache).unwrap();
+        let config = ProtectConfig { deny: vec![keep.clone()], allow: vec![PathBuf::from("/")] };
+        let policy = Policy::builtin().with_config(&config);
+
+        let rule = policy.blocking_rule(&keep.join("notes.txt")).unwrap();
+        assert_eq!(rule.kind, "deny list entry");
+        assert!(policy.blocking_rule(&cache.join("a.tmp")).is_none());
+        // The allow list overrides built-in rules but never the deny list.
+        assert!(policy.blocking_rule(Path::new("/etc/hosts")).is_none());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::age::TimeField;
+use crate::cleaner::deletion_failure;
+use crate::mounts;
+use crate::safe_delete;
+use crate::usage::human_size;
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+/// `$CARGO_HOME`, or `~/.cargo`.
+pub fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME").map(PathBuf::from).or_else(|| dirs::home_dir().map(|h| h.join(".cargo")))
+}
+
+/// `$RUSTUP_HOME`, or `~/.rustup`.
+pub fn rustup_home() -> Option<PathBuf> {
+    std::env::var_os("RUSTUP_HOME").map(PathBuf::from).or_else(|| dirs::home_dir().map(|h| h.join(".rustup")))
+}
+
+/// One of the directories that Cargo and rustup fill up.
+#[derive(Debug, Clone)]
+pub struct CacheArea {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// A crate archive or git checkout with the last time it was used.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub is_default: bool,
+}
+
+/// Size of a directory tree, and the latest access or modification time of
+/// the files in it. Directories are left out because listing them, as this
+/// scan does, updates their access time.
+fn usage_of(path: &Path) -> (u64, Option<DateTime<Utc>>) {
+    let mut size = 0;
+    let mut last_used = None;
+    for entry in mounts::walk(WalkDir::new(path)).flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Ok(metadata) = entry.metadata() {
+            size += metadata.len();
+            last_used = last_used.max(TimeField::Accessed.of(&metadata)).max(TimeField::Modified.of(&metadata));
+        }
+    }
+    (size, last_used)
+}
+
+fn subdirs(path: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(path)
+        .map(|entries| entries.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+pub fn cache_areas(cargo_home: &Path, rustup_home: &Path) -> Vec<CacheArea> {
+    [
+        ("Crate archives", cargo_home.join("registry").join("cache")),
+        ("Extracted crate sources", cargo_home.join("registry").join("src")),
+        ("Git checkouts", cargo_home.join("git").join("checkouts")),
+        ("Git databases", cargo_home.join("git").join("db")),
+        ("Toolchains", rustup_home.join("toolchains")),
+    ]
+    .into_iter()
+    .filter(|(_, path)| path.is_dir())
+    .map(|(name, path)| CacheArea { name, size: usage_of(&path).0, path })
+    .collect()
+}
+
+/// The `.crate` archives and git checkouts not used for `unused_for`. Each
+/// checkout of a revision is judged on its own.
+pub fn stale_entries(cargo_home: &Path, unused_for: Duration, now: DateTime<Utc>) -> Vec<CacheEntry> {
+    let mut candidates = Vec::new();
+    for index in subdirs(&cargo_home.join("registry").join("cache")) {
+        for entry in mounts::walk(WalkDir::new(&index).min_depth(1).max_depth(1)).flatten() {
+            if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "crate") {
+                safe_delete::record_entry(&entry);
+                candidates.push(entry.into_path());
+            }
+        }
+    }
+    for repo in subdirs(&cargo_home.join("git").join("checkouts")) {
+        for revision in subdirs(&repo) {
+            if let Ok(metadata) = fs::symlink_metadata(&revision) {
+                safe_delete::record(&revision, &metadata);
+            }
+            candidates.push(revision);
+        }
+    }
+
+    candidates
+        .into_iter()
+        .map(|path| {
+            let (size, last_used) = usage_of(&path);
+            CacheEntry { path, size, last_used }
+        })
+        .filter(|entry| entry.last_used.is_none_or(|time| now.signed_duration_since(time) > unused_for))
+        .collect()
+}
+
+/// The extracted sources of every registry, which Cargo extracts again from
+/// the crate archives when a build needs them.
+pub fn registry_sources(cargo_home: &Path) -> Vec<CacheEntry> {
+    subdirs(&cargo_home.join("registry").join("src"))
+        .into_iter()
+        .map(|path| {
+            if let Ok(metadata) = fs::symlink_metadata(&path) {
+                safe_delete::record(&path, &metadata);
+            }
+            let (size, last_used) = usage_of(&path);
+            CacheEntry { path, size, last_used }
+        })
+        .collect()
+}
+
+/// Reads `default_toolchain` from rustup's `settings.toml`.
+fn default_toolchain(rustup_home: &Path) -> Option<String> {
+    let settings = fs::read_to_string(rustup_home.join("settings.toml")).ok()?;
+    settings.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "default_toolchain").then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+pub fn toolchains(rustup_home: &Path) -> Vec<Toolchain> {
+    let default = default_toolchain(rustup_home);
+    subdirs(&rustup_home.join("toolchains"))
+        .into_iter()
+        .map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            // The default may be written without the host triple, e.g. "stable".
+            let is_default = default.as_ref().is_some_and(|d| name == *d || name.starts_with(&format!("{}-", d)));
+            Toolchain { size: usage_of(&path).0, name, path, is_default }
+        })
+        .collect()
+}
+
+pub fn print_cache_report(areas: &[CacheArea], toolchains: &[Toolchain]) {
+    let mut table = Table::new();
+    table.add_row(row!["Cache".bold().blue(), "Path".bold().blue(), "Size".bold().blue()]);
+    for area in areas {
+        table.add_row(Row::new(vec![
+            Cell::new(area.name),
+            Cell::new(&area.path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(area.size)),
+        ]));
+    }
+    table.printstd();
+
+    let mut table = Table::new();
+    table.add_row(row!["Toolchain".bold().blue(), "Size".bold().blue(), "Status".bold().blue()]);
+    for toolchain in toolchains {
+        let status = if toolchain.is_default { "Default".green() } else { "Not default".yellow() };
+        table.add_row(Row::new(vec![
+            Cell::new(&toolchain.name),
+            Cell::new(&human_size(toolchain.size)),
+            Cell::new(&status.to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+pub fn print_entries_report(entries: &[CacheEntry]) {
+    let mut table = Table::new();
+    table.add_row(row!["Path".bold().blue(), "Size".bold().blue(), "Last Used".bold().blue()]);
+    for entry in entries {
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(entry.size)),
+            Cell::new(&entry.last_used.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default()),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Deletes `entries`, files and directories alike, and returns the space freed.
+pub fn remove_entries(entries: &[CacheEntry]) -> u64 {
+    let mut table = Table::new();
+    table.add_row(row!["Path".bold().blue(), "Status".bold().blue()]);
+    let mut freed = 0;
+
+    for entry in entries {
+        let result = if entry.path.is_dir() {
+            safe_delete::remove_dir_all(&entry.path)
+        } else {
+            safe_delete::remove_file(&entry.path)
+        };
+        let status = match result {
+            Ok(_) => {
+                freed += entry.size;
+                "Deleted".to_string()
+            }
+            Err(e) => deletion_failure(&entry.path, &e),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.path.display().to_string().blue().to_string()),
+            Cell::new(&status.green().to_string()),
+        ]));
+    }
+
+    table.printstd();
+    println!("{}", format!("Space freed: {}", human_size(freed)).green());
+    freed
+}
+
+/// Removes toolchains with `rustup toolchain uninstall`, which also updates
+/// rustup's own records.
+pub fn uninstall_toolchains(toolchains: &[Toolchain]) -> u64 {
+    let mut table = Table::new();
+    table.add_row(row!["Toolchain".bold().blue(), "Status".bold().blue()]);
+    let mut freed = 0;
+
+    for toolchain in toolchains {
+        let status = match Command::new("rustup").args(["toolchain", "uninstall", &toolchain.name]).status() {
+            Ok(status) if status.success() => {
+                if !toolchain.path.exists() {
+                    freed += toolchain.size;
+                }
+                "Uninstalled".to_string()
+            }
+            Ok(status) => {
+                eprintln!("{}", format!("rustup failed to uninstall {}: {}", toolchain.name, status).red());
+                "Failed to uninstall".to_string()
+            }
+            Err(e) => {
+                eprintln!("{}", format!("Failed to run rustup: {}", e).red());
+                "Failed to uninstall".to_string()
+            }
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&toolchain.name),
+            Cell::new(&status.green().to_string()),
+        ]));
+    }
+
+    table.printstd();
+    println!("{}", format!("Space freed: {}", human_size(freed)).green());
+    freed
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_stale_entries_sources_and_toolchains() {
+        let cargo = tempdir().unwrap();
+        let rustup = tempdir().unwrap();
+        let cache = cargo.path().join("registry").join("cache").join("index.crates.io-abc");
+        let src = cargo.path().join("registry").join("src").join("index.crates.io-abc").join("serde-1.0.0");
+        let checkout = cargo.path().join("git").join("checkouts").join("ratatui-123");
+        for dir in [&cache, &src, &checkout.join("aaa"), &checkout.join("bbb")] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(cache.join("serde-1.0.0.crate"), vec![0u8; 100]).unwrap();
+        fs::write(cache.join("rand-0.8.0.crate"), vec![0u8; 200]).unwrap();
+        fs::write(src.join("lib.rs"), vec![0u8; 400]).unwrap();
+        fs::write(checkout.join("aaa").join("lib.rs"), vec![0u8; 10]).unwrap();
+        fs::write(checkout.join("bbb").join("lib.rs"), vec![0u8; 20]).unwrap();
+
+        let old = FileTime::from_system_time(SystemTime::now() - std::time::Duration::from_secs(200 * 86400));
+        for path in [cache.join("serde-1.0.0.crate"), checkout.join("aaa").join("lib.rs")] {
+            filetime::set_file_times(&path, old, old).unwrap();
+        }
+
+        let stale = stale_entries(cargo.path(), Duration::days(90), Utc::now());
+        let paths: Vec<&Path> = stale.iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(paths, vec![cache.join("serde-1.0.0.crate"), checkout.join("aaa")]);
+
+        let sources = registry_sources(cargo.path());
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].size, 400);
+        assert_eq!(remove_entries(&stale), 110);
+        assert!(!checkout.join("aaa").exists() && checkout.join("bbb").exists());
+
+        fs::write(rustup.path().join("settings.toml"), "version = \"12\"\ndefault_toolchain = \"stable\"\n").unwrap();
+        fs::create_dir_all(rustup.path().join("toolchains").join("stable-x86_64-unknown-linux-gnu")).unwrap();
+        fs::create_dir_all(rustup.path().join("toolchains").join("nightly-x86_64-unknown-linux-gnu")).unwrap();
+        let toolchains = toolchains(rustup.path());
+        let defaults: Vec<(&str, bool)> = toolchains.iter().map(|t| (t.name.as_str(), t.is_default)).collect();
+        assert_eq!(defaults, vec![("nightly-x86_64-unknown-linux-gnu", false), ("stable-x86_64-unknown-linux-gnu", true)]);
+
+        let areas = cache_areas(cargo.path(), rustup.path());
+        assert_eq!(areas.iter().map(|a| a.name).collect::<Vec<_>>(), vec!["Crate archives", "Extracted crate sources", "Git checkouts", "Toolchains"]);
+        assert_eq!(areas[0].size, 200);
+    }
+}
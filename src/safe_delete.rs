@@ -0,0 +1,413 @@
+// Scanners record the device and inode of every entry they report, and the
+// directory their walk started from. Deleting opens that scan root and then
+// each directory below it without following symlinks, checks the entry
+// against the scan record and unlinks it relative to the last directory
+// handle, so a directory swapped for a symlink inside the scanned tree after
+// the scan cannot redirect the deletion. Files that no scan recorded are not
+// deleted. Every function that deletes or overwrites refuses protected paths.
+
+use crate::protect;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use walkdir::DirEntry;
+
+/// Error payload used when an entry is no longer the one that was scanned,
+/// or was never recorded by a scan.
+#[derive(Debug)]
+pub enum ChangedSinceScan {
+    Replaced,
+    NotScanned,
+}
+
+impl fmt::Display for ChangedSinceScan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangedSinceScan::Replaced => write!(f, "file changed since it was scanned"),
+            ChangedSinceScan::NotScanned => write!(f, "file was not found by a scan"),
+        }
+    }
+}
+
+impl std::error::Error for ChangedSinceScan {}
+
+pub fn is_changed_since_scan(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|e| e.is::<ChangedSinceScan>())
+}
+
+pub fn is_not_scanned(err: &io::Error) -> bool {
+    err.get_ref().and_then(|e| e.downcast_ref::<ChangedSinceScan>()).is_some_and(|e| matches!(e, ChangedSinceScan::NotScanned))
+}
+
+fn changed_since_scan() -> io::Error {
+    io::Error::other(ChangedSinceScan::Replaced)
+}
+
+/// Device and inode of a scanned entry, and how many levels below the root
+/// of its scan it was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Scanned {
+    id: (u64, u64),
+    depth: usize,
+}
+
+fn scan_record() -> &'static Mutex<HashMap<PathBuf, Scanned>> {
+    static RECORD: OnceLock<Mutex<HashMap<PathBuf, Scanned>>> = OnceLock::new();
+    RECORD.get_or_init(Default::default)
+}
+
+#[cfg(unix)]
+fn insert(path: &Path, metadata: &fs::Metadata, depth: usize) {
+    use std::os::unix::fs::MetadataExt;
+    let scanned = Scanned { id: (metadata.dev(), metadata.ino()), depth };
+    scan_record().lock().unwrap().insert(path.to_path_buf(), scanned);
+}
+
+#[cfg(not(unix))]
+fn insert(_path: &Path, _metadata: &fs::Metadata, _depth: usize) {}
+
+/// Remembers the identity of `path`, found directly in its parent directory.
+pub fn record(path: &Path, metadata: &fs::Metadata) {
+    insert(path, metadata, 1);
+}
+
+/// Remembers the identity of a walk entry, found below the walk's root.
+pub fn record_walked(entry: &DirEntry, metadata: &fs::Metadata) {
+    insert(entry.path(), metadata, entry.depth());
+}
+
+/// Records a walk entry; entries whose metadata cannot be read are left out
+/// and cannot be deleted.
+pub fn record_entry(entry: &DirEntry) {
+    if let Ok(metadata) = entry.metadata() {
+        record_walked(entry, &metadata);
+    }
+}
+
+/// Forgets every recorded entry, so that each action only deletes what its
+/// own scan found.
+pub fn clear_record() {
+    scan_record().lock().unwrap().clear();
+}
+
+fn recorded(path: &Path) -> Option<Scanned> {
+    scan_record().lock().unwrap().get(path).copied()
+}
+
+fn forget(path: &Path) {
+    scan_record().lock().unwrap().remove(path);
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::*;
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::ffi::OsStrExt;
+
+    pub fn cstring(s: &OsStr) -> io::Result<CString> {
+        CString::new(s.as_bytes()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+    }
+
+    fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+        if ret < 0 { Err(io::Error::last_os_error()) } else { Ok(ret) }
+    }
+
+    /// Opens the directory containing `path` and returns it with the final
+    /// component. `root` is opened as given; every directory from there down
+    /// to the parent is opened without following a symlink in its place.
+    pub fn open_parent(path: &Path, root: &Path) -> io::Result<(OwnedFd, CString)> {
+        use std::path::Component;
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let name = path.file_name().ok_or_else(|| invalid("path has no file name"))?;
+        let below = path.strip_prefix(root).map_err(|_| invalid("path is outside its scan root"))?;
+        let root = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+        let root = cstring(root.as_os_str())?;
+        let fd = check(unsafe { libc::open(root.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC) })?;
+        let mut dir = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut components: Vec<Component> = below.components().filter(|c| *c != Component::CurDir).collect();
+        components.pop();
+        for component in components {
+            let Component::Normal(component) = component else {
+                return Err(invalid("path is not below its scan root"));
+            };
+            dir = open_at(dir.as_raw_fd(), &cstring(component)?, libc::O_RDONLY | libc::O_DIRECTORY)?;
+        }
+        Ok((dir, cstring(name)?))
+    }
+
+    pub fn stat_at(dir: RawFd, name: &CStr) -> io::Result<libc::stat> {
+        let mut st: libc::stat = unsafe { std::mem::zeroed() };
+        check(unsafe { libc::fstatat(dir, name.as_ptr(), &mut st, libc::AT_SYMLINK_NOFOLLOW) })?;
+        Ok(st)
+    }
+
+    pub fn is_dir(st: &libc::stat) -> bool {
+        st.st_mode & libc::S_IFMT == libc::S_IFDIR
+    }
+
+    // The field types differ between platforms (dev_t is 32-bit on macOS).
+    #[allow(clippy::unnecessary_cast)]
+    pub fn identity(st: &libc::stat) -> (u64, u64) {
+        (st.st_dev as u64, st.st_ino as u64)
+    }
+
+    pub fn unlink_at(dir: RawFd, name: &CStr, remove_dir: bool) -> io::Result<()> {
+        let flags = if remove_dir { libc::AT_REMOVEDIR } else { 0 };
+        check(unsafe { libc::unlinkat(dir, name.as_ptr(), flags) }).map(|_| ())
+    }
+
+    pub fn open_at(dir: RawFd, name: &CStr, flags: libc::c_int) -> io::Result<OwnedFd> {
+        let fd = check(unsafe { libc::openat(dir, name.as_ptr(), flags | libc::O_NOFOLLOW | libc::O_CLOEXEC) })?;
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    fn list_dir(dir: &OwnedFd) -> io::Result<Vec<CString>> {
+        // fdopendir takes ownership of the descriptor it is given.
+        let dup = check(unsafe { libc::fcntl(dir.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) })?;
+        let stream = unsafe { libc::fdopendir(dup) };
+        if stream.is_null() {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(dup) };
+            return Err(err);
+        }
+        let mut names = Vec::new();
+        loop {
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                break;
+            }
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            if name.to_bytes() != b"." && name.to_bytes() != b".." {
+                names.push(name.to_owned());
+            }
+        }
+        unsafe { libc::closedir(stream) };
+        Ok(names)
+    }
+
+    /// Empties the directory open at `dir` without following any symlink.
+    pub fn remove_contents(dir: &OwnedFd) -> io::Result<()> {
+        for name in list_dir(dir)? {
+            let st = stat_at(dir.as_raw_fd(), &name)?;
+            if is_dir(&st) {
+                let child = open_at(dir.as_raw_fd(), &name, libc::O_RDONLY | libc::O_DIRECTORY)?;
+                remove_contents(&child)?;
+                unlink_at(dir.as_raw_fd(), &name, true)?;
+            } else {
+                unlink_at(dir.as_raw_fd(), &name, false)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The directory a scan of `path` started from. The parent of a scan root
+/// is opened as given, like the root of a depth-one entry.
+fn scan_root(path: &Path, scanned: Scanned) -> &Path {
+    path.ancestors().nth(scanned.depth.max(1)).unwrap_or(Path::new(""))
+}
+
+/// Opens the entry at `path` and checks it against the scan record.
+#[cfg(unix)]
+fn open_checked(path: &Path) -> io::Result<(std::os::fd::OwnedFd, std::ffi::CString, libc::stat)> {
+    use std::os::fd::AsRawFd;
+    let scanned = recorded(path).ok_or_else(|| io::Error::other(ChangedSinceScan::NotScanned))?;
+    let (dir, name) = sys::open_parent(path, scan_root(path, scanned))?;
+    let st = sys::stat_at(dir.as_raw_fd(), &name)?;
+    if scanned.id != sys::identity(&st) {
+        return Err(changed_since_scan());
+    }
+    Ok((dir, name, st))
+}
+
+/// Removes the file or symlink at `path`.
+#[cfg(unix)]
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    protect::check(path)?;
+    let (dir, name, st) = open_checked(path)?;
+    if sys::is_dir(&st) {
+        return Err(io::Error::from_raw_os_error(libc::EISDIR));
+    }
+    sys::unlink_at(dir.as_raw_fd(), &name, false)?;
+    forget(path);
+    Ok(())
+}
+
+/// Removes the directory at `path` if it is empty. Directories that no scan
+/// recorded, such as those emptied by a clean, are reached from the
+/// filesystem root without following any symlink.
+#[cfg(unix)]
+pub fn remove_dir(path: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    protect::check(path)?;
+    let dir = match recorded(path) {
+        Some(_) => open_checked(path)?.0,
+        None => sys::open_parent(path, path.ancestors().last().unwrap_or(Path::new("")))?.0,
+    };
+    let name = sys::cstring(path.file_name().unwrap_or_default())?;
+    sys::unlink_at(dir.as_raw_fd(), &name, true)?;
+    forget(path);
+    Ok(())
+}
+
+/// Removes the directory at `path` and everything below it. Symlinks inside
+/// the tree are unlinked, never followed.
+#[cfg(unix)]
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    protect::check(path)?;
+    let (dir, name, st) = open_checked(path)?;
+    if !sys::is_dir(&st) {
+        return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+    }
+    let target = sys::open_at(dir.as_raw_fd(), &name, libc::O_RDONLY | libc::O_DIRECTORY)?;
+    sys::remove_contents(&target)?;
+    sys::unlink_at(dir.as_raw_fd(), &name, true)?;
+    forget(path);
+    Ok(())
+}
+
+/// Opens the regular file at `path` for reading or writing without following
+/// a symlink, after checking it against the scan record.
+#[cfg(unix)]
+pub fn open_file(path: &Path, write: bool) -> io::Result<fs::File> {
+    use std::os::fd::AsRawFd;
+    if write {
+        protect::check(path)?;
+    }
+    let (dir, name, st) = open_checked(path)?;
+    let flags = if write { libc::O_WRONLY } else { libc::O_RDONLY };
+    let file = fs::File::from(sys::open_at(dir.as_raw_fd(), &name, flags)?);
+    // The name could have been replaced between the check and the open.
+    let opened = file.metadata()?;
+    if !opened.is_file() || {
+        use std::os::unix::fs::MetadataExt;
+        (opened.dev(), opened.ino()) != sys::identity(&st)
+    } {
+        return Err(changed_since_scan());
+    }
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+pub fn remove_file(path: &Path) -> io::Result<()> {
+    protect::check(path)?;
+    fs::remove_file(path)
+}
+
+#[cfg(not(unix))]
+pub fn remove_dir(path: &Path) -> io::Result<()> {
+    protect::check(path)?;
+    fs::remove_dir(path)
+}
+
+#[cfg(not(unix))]
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    protect::check(path)?;
+    fs::remove_dir_all(path)
+}
+
+#[cfg(not(unix))]
+pub fn open_file(path: &Path, write: bool) -> io::Result<fs::File> {
+    if write {
+        protect::check(path)?;
+    }
+    fs::OpenOptions::new().read(!write).write(write).open(path)
+}
+
+// Tests Unitaires
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_swapped_directory_is_not_followed() {
+        let root = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let sub = root.path().join("sub");
+        let file = sub.join("app.log");
+        fs::create_dir(&sub).unwrap();
+        fs::write(&file, "scanned").unwrap();
+        fs::write(outside.path().join("app.log"), "precious").unwrap();
+        record(&file, &fs::symlink_metadata(&file).unwrap());
+
+        // Replace the scanned directory with a symlink pointing elsewhere.
+        fs::remove_dir_all(&sub).unwrap();
+        symlink(outside.path(), &sub).unwrap();
+
+        assert!(remove_file(&file).is_err());
+        assert!(open_file(&file, true).is_err());
+        assert_eq!(fs::read_to_string(outside.path().join("app.log")).unwrap(), "precious");
+    }
+
+    #[test]
+    fn test_swapped_ancestor_directory_is_not_followed() {
+        let root = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let file = root.path().join("a").join("b").join("app.log");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "scanned").unwrap();
+        for entry in walkdir::WalkDir::new(root.path()) {
+            record_entry(&entry.unwrap());
+        }
+
+        // Move the scanned tree away and point its first directory at a copy
+        // holding the very same file.
+        fs::create_dir(outside.path().join("b")).unwrap();
+        fs::rename(&file, outside.path().join("b").join("app.log")).unwrap();
+        fs::remove_dir_all(root.path().join("a")).unwrap();
+        symlink(outside.path(), root.path().join("a")).unwrap();
+
+        assert!(remove_file(&file).is_err());
+        assert!(outside.path().join("b").join("app.log").exists());
+    }
+
+    #[test]
+    fn test_unrecorded_file_is_refused() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("cache.tmp");
+        fs::write(&file, "never scanned").unwrap();
+
+        assert!(is_changed_since_scan(&remove_file(&file).unwrap_err()));
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn test_replaced_file_is_refused() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("cache.tmp");
+        fs::write(&file, "old").unwrap();
+        record(&file, &fs::symlink_metadata(&file).unwrap());
+        let keep = dir.path().join("keep");
+        fs::rename(&file, &keep).unwrap();
+        fs::write(&file, "new").unwrap();
+
+        let err = remove_file(&file).unwrap_err();
+        assert!(is_changed_since_scan(&err));
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn test_remove_dir_all_unlinks_symlinks_without_following() {
+        let root = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let tree = root.path().join("tree");
+        fs::create_dir_all(tree.join("nested")).unwrap();
+        fs::write(tree.join("nested").join("a.txt"), "a").unwrap();
+        fs::write(outside.path().join("b.txt"), "b").unwrap();
+        symlink(outside.path(), tree.join("nested").join("link")).unwrap();
+        record(&tree, &fs::symlink_metadata(&tree).unwrap());
+
+        remove_dir_all(&tree).unwrap();
+
+        assert!(!tree.exists());
+        assert!(outside.path().join("b.txt").exists());
+    }
+}
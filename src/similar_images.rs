@@ -0,0 +1,388 @@
+use crate::cleaner::{has_permission, is_permission_denied};
+use crate::mounts;
+use crate::safe_delete;
+use colored::*;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use prettytable::{Table, Row, Cell, row};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Perceptual hash used to compare images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// Average hash: pixels of an 8x8 thumbnail compared with their mean.
+    Average,
+    /// Difference hash: horizontal gradients of a 9x8 thumbnail.
+    Difference,
+    /// Perceptual hash: low frequencies of the DCT of a 32x32 thumbnail.
+    Perceptual,
+}
+
+impl HashAlgorithm {
+    pub fn from_name(name: &str) -> Result<HashAlgorithm, String> {
+        match name {
+            "ahash" => Ok(HashAlgorithm::Average),
+            "dhash" => Ok(HashAlgorithm::Difference),
+            "phash" => Ok(HashAlgorithm::Perceptual),
+            _ => Err(format!("unknown image hash algorithm: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub size: u64,
+    pub hash: u64,
+}
+
+/// Images within the Hamming distance threshold of the copy worth keeping
+/// (highest resolution, then largest file).
+#[derive(Debug, Clone)]
+pub struct SimilarGroup {
+    pub images: Vec<ImageInfo>,
+    pub best: usize,
+}
+
+impl SimilarGroup {
+    pub fn best(&self) -> &ImageInfo {
+        &self.images[self.best]
+    }
+
+    pub fn others(&self) -> impl Iterator<Item = &ImageInfo> {
+        let best = self.best;
+        self.images.iter().enumerate().filter(move |(i, _)| *i != best).map(|(_, img)| img)
+    }
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn grayscale(img: &DynamicImage, width: u32, height: u32) -> Vec<f64> {
+    img.resize_exact(width, height, FilterType::Triangle)
+        .to_luma8()
+        .pixels()
+        .map(|p| p.0[0] as f64)
+        .collect()
+}
+
+fn bits_above(values: &[f64], threshold: f64) -> u64 {
+    values
+        .iter()
+        .enumerate()
+        .fold(0u64, |hash, (i, v)| if *v > threshold { hash | (1 << i) } else { hash })
+}
+
+fn dct_1d(input: &[f64], output: &mut [f64]) {
+    let n = input.len() as f64;
+    for (k, out) in output.iter_mut().enumerate() {
+        *out = input
+            .iter()
+            .enumerate()
+            .map(|(i, x)| x * (std::f64::consts::PI / n * (i as f64 + 0.5) * k as f64).cos())
+            .sum();
+    }
+}
+
+pub fn image_hash(img: &DynamicImage, algorithm: HashAlgorithm) -> u64 {
+    match algorithm {
+        HashAlgorithm::Average => {
+            let pixels = grayscale(img, 8, 8);
+            let mean = pixels.iter().sum::<f64>() / pixels.len() as f64;
+            bits_above(&pixels, mean)
+        }
+        HashAlgorithm::Difference => {
+            let pixels = grayscale(img, 9, 8);
+            let mut hash = 0u64;
+            for y in 0..8 {
+                for x in 0..8 {
+                    if pixels[y * 9 + x] > pixels[y * 9 + x + 1] {
+                        hash |= 1 << (y * 8 + x);
+                    }
+                }
+            }
+            hash
+        }
+        HashAlgorithm::Perceptual => {
+            const SIZE: usize = 32;
+            let pixels = grayscale(img, SIZE as u32, SIZE as u32);
+            let mut rows = vec![0.0; SIZE * SIZE];
+            for y in 0..SIZE {
+                dct_1d(&pixels[y * SIZE..(y + 1) * SIZE], &mut rows[y * SIZE..(y + 1) * SIZE]);
+            }
+            let mut low = Vec::with_capacity(64);
+            let mut column = vec![0.0; SIZE];
+            let mut transformed = vec![0.0; SIZE];
+            for x in 0..8 {
+                for y in 0..SIZE {
+                    column[y] = rows[y * SIZE + x];
+                }
+                dct_1d(&column, &mut transformed);
+                low.extend_from_slice(&transformed[..8]);
+            }
+            // The DC term only reflects overall brightness, so it is left out
+            // of the median.
+            let mut sorted: Vec<f64> = low[1..].to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let median = sorted[sorted.len() / 2];
+            bits_above(&low, median)
+        }
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+fn load_image(path: &Path, algorithm: HashAlgorithm) -> Result<ImageInfo, String> {
+    let img = image::open(path).map_err(|e| e.to_string())?;
+    let (width, height) = img.dimensions();
+    let size = std::fs::metadata(path).map(|m| m.len()).map_err(|e| e.to_string())?;
+    Ok(ImageInfo { path: path.to_path_buf(), width, height, size, hash: image_hash(&img, algorithm) })
+}
+
+/// BK-tree over image hashes, to find the images within a Hamming distance
+/// of a hash without comparing it with every image.
+struct BkTree {
+    /// Image index and children keyed by their distance to it.
+    nodes: Vec<(usize, Vec<(u32, usize)>)>,
+}
+
+impl BkTree {
+    fn new(images: &[ImageInfo]) -> BkTree {
+        let mut tree = BkTree { nodes: Vec::with_capacity(images.len()) };
+        for index in 0..images.len() {
+            tree.insert(images, index);
+        }
+        tree
+    }
+
+    fn insert(&mut self, images: &[ImageInfo], index: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push((index, Vec::new()));
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let distance = hamming_distance(images[self.nodes[node].0].hash, images[index].hash);
+            match self.nodes[node].1.iter().find(|(d, _)| *d == distance) {
+                Some(&(_, child)) => node = child,
+                None => {
+                    self.nodes.push((index, Vec::new()));
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].1.push((distance, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Indices of the images at most `max_distance` bits away from `hash`.
+    fn within(&self, images: &[ImageInfo], hash: u64, max_distance: u32) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = if self.nodes.is_empty() { Vec::new() } else { vec![0] };
+        while let Some(node) = stack.pop() {
+            let (index, children) = &self.nodes[node];
+            let distance = hamming_distance(images[*index].hash, hash);
+            if distance <= max_distance {
+                found.push(*index);
+            }
+            stack.extend(children.iter()
+                .filter(|(d, _)| d.abs_diff(distance) <= max_distance)
+                .map(|(_, child)| *child));
+        }
+        found
+    }
+}
+
+/// Groups images around the copy worth keeping: the best remaining image
+/// (highest resolution, then largest file) takes every remaining image at
+/// most `max_distance` bits from it. Every member is therefore close to the
+/// kept copy, and a chain of images that are each close to the next is not
+/// merged into one group.
+pub fn group_similar(images: Vec<ImageInfo>, max_distance: u32) -> Vec<SimilarGroup> {
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    order.sort_by(|&a, &b| {
+        let quality = |img: &ImageInfo| (img.width as u64 * img.height as u64, img.size);
+        quality(&images[b]).cmp(&quality(&images[a])).then_with(|| images[a].path.cmp(&images[b].path))
+    });
+    let tree = BkTree::new(&images);
+    let mut assigned = vec![false; images.len()];
+
+    let mut members: Vec<(usize, Vec<usize>)> = Vec::new();
+    for best in order {
+        if assigned[best] {
+            continue;
+        }
+        assigned[best] = true;
+        let mut others: Vec<usize> = tree.within(&images, images[best].hash, max_distance)
+            .into_iter()
+            .filter(|&i| !assigned[i])
+            .collect();
+        for &i in &others {
+            assigned[i] = true;
+        }
+        if !others.is_empty() {
+            others.sort_by(|&a, &b| images[a].path.cmp(&images[b].path));
+            members.push((best, others));
+        }
+    }
+
+    let mut groups: Vec<SimilarGroup> = members
+        .into_iter()
+        .map(|(best, others)| {
+            let mut group: Vec<ImageInfo> = std::iter::once(best).chain(others).map(|i| images[i].clone()).collect();
+            let best_path = group[0].path.clone();
+            group.sort_by(|a, b| a.path.cmp(&b.path));
+            let best = group.iter().position(|img| img.path == best_path).unwrap_or(0);
+            SimilarGroup { images: group, best }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.images[0].path.cmp(&b.images[0].path));
+    groups
+}
+
+pub fn find_similar_images(dirs_to_scan: &[PathBuf], exclude_dirs: &HashSet<String>, algorithm: HashAlgorithm, max_distance: u32) -> Vec<SimilarGroup> {
+    let mut paths = Vec::new();
+    for dir in dirs_to_scan {
+        if !exclude_dirs.contains(dir.to_str().unwrap()) && has_permission(dir) {
+            for entry in mounts::walk(WalkDir::new(dir)) {
+                match entry {
+                    Ok(entry) => {
+                        if entry.file_type().is_file() && is_image(entry.path()) {
+                            safe_delete::record_entry(&entry);
+                            paths.push(entry.path().to_path_buf());
+                        }
+                    }
+                    Err(e) => {
+                        if is_permission_denied(&e) {
+                            eprintln!("{}", format!("Permission denied: {}", e).red());
+                        } else {
+                            eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let images: Vec<ImageInfo> = paths
+        .par_iter()
+        .filter_map(|path| match load_image(path, algorithm) {
+            Ok(info) => Some(info),
+            Err(e) => {
+                eprintln!("{}", format!("Failed to decode {}: {}", path.display(), e).yellow());
+                None
+            }
+        })
+        .collect();
+
+    group_similar(images, max_distance)
+}
+
+pub fn print_similar_images_report(groups: &[SimilarGroup]) {
+    let mut table = Table::new();
+    table.add_row(row!["Group".bold().blue(), "File Path".bold().blue(), "Resolution".bold().blue(), "Size".bold().blue(), "Distance".bold().blue(), "Status".bold().blue()]);
+
+    for (index, group) in groups.iter().enumerate() {
+        let best_hash = group.best().hash;
+        for (i, image) in group.images.iter().enumerate() {
+            let status = if i == group.best {
+                "Best copy".green().to_string()
+            } else {
+                "Similar".yellow().to_string()
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&(index + 1).to_string()),
+                Cell::new(&image.path.to_str().unwrap().blue().to_string()),
+                Cell::new(&format!("{}x{}", image.width, image.height)),
+                Cell::new(&format!("{} bytes", image.size)),
+                Cell::new(&hamming_distance(image.hash, best_hash).to_string()),
+                Cell::new(&status),
+            ]));
+        }
+    }
+
+    table.printstd();
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+    use tempfile::tempdir;
+
+    fn pattern(size: u32, invert: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(size, size, |x, y| {
+            let (fx, fy) = (x as f64 / size as f64, y as f64 / size as f64);
+            let wave = (fx * 7.0).sin() * (fy * 5.0 + 1.0).cos() + (fx * fy * 9.0).sin();
+            let v = (128.0 + 60.0 * wave).clamp(0.0, 255.0) as u8;
+            let v = if invert { 255 - v } else { v };
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn test_resized_copies_are_similar_for_every_algorithm() {
+        let original = pattern(128, false);
+        let resized = original.resize_exact(48, 48, FilterType::Lanczos3);
+        let different = pattern(128, true);
+
+        for algorithm in [HashAlgorithm::Average, HashAlgorithm::Difference, HashAlgorithm::Perceptual] {
+            let a = image_hash(&original, algorithm);
+            assert!(hamming_distance(a, image_hash(&resized, algorithm)) <= 6, "{:?}", algorithm);
+            assert!(hamming_distance(a, image_hash(&different, algorithm)) > 16, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_chained_images_are_not_grouped_together() {
+        let image = |name: &str, width: u32, hash: u64| ImageInfo { path: PathBuf::from(name), width, height: width, size: 1000, hash };
+        // b is 4 bits from a and from c, but c is 8 bits from a.
+        let images = vec![image("a.png", 200, 0), image("b.png", 100, 0x0F), image("c.png", 100, 0xFF)];
+
+        let groups = group_similar(images.clone(), 4);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].best().path, PathBuf::from("a.png"));
+        assert_eq!(groups[0].others().map(|i| i.path.clone()).collect::<Vec<_>>(), vec![PathBuf::from("b.png")]);
+        assert!(groups.iter().all(|g| g.others().all(|i| hamming_distance(i.hash, g.best().hash) <= 4)));
+
+        // Kept around b, all three are close to the kept copy.
+        let mut images = images;
+        images[1].width = 400;
+        images[1].height = 400;
+        let groups = group_similar(images, 4);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].images.len(), 3);
+        assert_eq!(groups[0].best().path, PathBuf::from("b.png"));
+    }
+
+    #[test]
+    fn test_find_similar_images_keeps_largest_resolution() {
+        let dir = tempdir().unwrap();
+        let large = dir.path().join("photo.png");
+        let small = dir.path().join("photo-small.jpg");
+        let other = dir.path().join("other.png");
+        pattern(128, false).save(&large).unwrap();
+        pattern(128, false).resize_exact(64, 64, FilterType::Lanczos3).to_rgb8().save(&small).unwrap();
+        pattern(128, true).save(&other).unwrap();
+
+        let groups = find_similar_images(&[dir.path().to_path_buf()], &HashSet::new(), HashAlgorithm::Perceptual, 8);
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].images.len(), 2);
+        assert_eq!(groups[0].best().path, large);
+        assert_eq!(groups[0].others().next().unwrap().path, small);
+    }
+}
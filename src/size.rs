@@ -0,0 +1,182 @@
+use crate::age::AgeRule;
+use crate::cleaner::{has_permission, is_permission_denied};
+use crate::filter;
+use crate::mounts;
+use crate::safe_delete;
+use crate::usage::human_size;
+use chrono::Utc;
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use walkdir::WalkDir;
+
+/// Parses a size such as `500K`, `100MB`, `2G` or `1.5GiB`. Units are binary
+/// (`K` is 1024 bytes) and a number alone is a number of bytes.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Invalid size '{}', expected e.g. 500K, 100M or 2G", text))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("Unknown size unit '{}', expected B, K, M, G or T", unit.trim())),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Bounds on the size of the files a scan selects, both inclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeFilter {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl SizeFilter {
+    pub fn matches(&self, size: u64) -> bool {
+        self.min.is_none_or(|min| size >= min) && self.max.is_none_or(|max| size <= max)
+    }
+}
+
+fn active_filter() -> &'static RwLock<SizeFilter> {
+    static FILTER: RwLock<SizeFilter> = RwLock::new(SizeFilter { min: None, max: None });
+    &FILTER
+}
+
+/// Replaces the size bounds applied by the file scans.
+pub fn set_size_filter(filter: SizeFilter) {
+    *active_filter().write().unwrap() = filter;
+}
+
+/// Whether a file with `metadata` passes the size bounds of the scans.
+pub fn accepts(metadata: &fs::Metadata) -> bool {
+    active_filter().read().unwrap().matches(metadata.len())
+}
+
+/// Version control metadata, whose empty files belong to the repository.
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn", ".bzr", "_darcs", "CVS"];
+
+/// Files that are empty on purpose: package and directory markers.
+const MARKER_FILES: &[&str] = &["__init__.py", "py.typed", ".gitkeep", ".keep", ".nojekyll"];
+
+/// Whether an empty file is a marker or a lock file, whose presence alone
+/// means something to the program that created it.
+fn is_meaningful_when_empty(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    MARKER_FILES.contains(&name) || name.eq_ignore_ascii_case("lock") || ext.eq_ignore_ascii_case("lock") || ext.eq_ignore_ascii_case("lck")
+}
+
+/// Finds the zero-byte regular files under `dirs_to_scan`, optionally only
+/// those with one of `extensions` or in the age range of `age`. Version
+/// control directories, marker files such as `__init__.py` and lock files
+/// are left out.
+pub fn find_empty_files<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>, exclude_types: &HashSet<String>, extensions: &HashSet<String>, age: Option<&AgeRule>) -> Vec<PathBuf> {
+    let now = Utc::now();
+    let mut empty_files = Vec::new();
+
+    for dir in dirs_to_scan {
+        let dir = dir.as_ref();
+        if exclude_dirs.contains(dir.to_str().unwrap_or_default()) || !has_permission(dir) {
+            continue;
+        }
+        let walker = mounts::walk_filtered(WalkDir::new(dir), |e| {
+            let is_vcs = e.file_type().is_dir() && e.depth() > 0 && VCS_DIRS.iter().any(|vcs| e.file_name() == *vcs);
+            !is_vcs && !exclude_dirs.contains(e.path().to_str().unwrap_or_default())
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if !entry.file_type().is_file() || is_meaningful_when_empty(entry.path()) {
+                        continue;
+                    }
+                    let ext = entry.path().extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+                    if exclude_types.contains(&ext) || (!extensions.is_empty() && !extensions.contains(&ext)) {
+                        continue;
+                    }
+                    if let Ok(metadata) = entry.metadata() {
+                        if metadata.len() == 0 && age.is_none_or(|rule| rule.matches(&metadata, now)) && filter::accepts(entry.path(), &metadata) {
+                            safe_delete::record_walked(&entry, &metadata);
+                            empty_files.push(entry.path().to_path_buf());
+                        }
+                    }
+                }
+                Err(e) => {
+                    if is_permission_denied(&e) {
+                        eprintln!("{}", format!("Permission denied: {}", e).red());
+                    } else {
+                        eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                    }
+                }
+            }
+        }
+    }
+
+    empty_files.sort();
+    empty_files
+}
+
+pub fn print_empty_files_report(empty_files: &[PathBuf]) {
+    let mut table = Table::new();
+    table.add_row(row!["File Path".bold().blue(), "Size".bold().blue()]);
+    for path in empty_files {
+        table.add_row(Row::new(vec![
+            Cell::new(&path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(0)),
+        ]));
+    }
+    table.printstd();
+    println!("{}", format!("Empty files: {}", empty_files.len()).green());
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_size_and_filter() {
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("100MB"), Ok(100 * 1024 * 1024));
+        assert_eq!(parse_size("1.5GiB"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("42"), Ok(42));
+        assert!(parse_size("2X").is_err());
+        assert!(parse_size("G").is_err());
+
+        let filter = SizeFilter { min: Some(100), max: Some(1000) };
+        assert!(filter.matches(100) && filter.matches(1000));
+        assert!(!filter.matches(99) && !filter.matches(1001));
+        assert!(SizeFilter::default().matches(0));
+    }
+
+    #[test]
+    fn test_find_empty_files() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("empty.tmp"), "").unwrap();
+        fs::write(dir.path().join("sub").join("empty.txt"), "").unwrap();
+        fs::write(dir.path().join("full.tmp"), "data").unwrap();
+        // Empty on purpose, or owned by version control.
+        fs::create_dir_all(dir.path().join("pkg")).unwrap();
+        fs::create_dir_all(dir.path().join(".git").join("refs")).unwrap();
+        for marker in ["pkg/__init__.py", "pkg/py.typed", "sub/.gitkeep", ".nojekyll", "db.lock", "LOCK", ".git/refs/empty"] {
+            fs::write(dir.path().join(marker), "").unwrap();
+        }
+
+        let all = find_empty_files(&[dir.path()], &HashSet::new(), &HashSet::new(), &HashSet::new(), None);
+        assert_eq!(all, vec![dir.path().join("empty.tmp"), dir.path().join("sub").join("empty.txt")]);
+
+        let tmp = find_empty_files(&[dir.path()], &HashSet::new(), &HashSet::new(), &["tmp".to_string()].into(), None);
+        assert_eq!(tmp, vec![dir.path().join("empty.tmp")]);
+
+        let no_txt = find_empty_files(&[dir.path()], &HashSet::new(), &["txt".to_string()].into(), &HashSet::new(), None);
+        assert_eq!(no_txt, vec![dir.path().join("empty.tmp")]);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::cleaner::{has_permission, is_permission_denied};
+use crate::mounts;
+use crate::safe_delete;
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Why a symbolic link no longer resolves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokenReason {
+    /// The target, or one of the directories leading to it, does not exist.
+    MissingTarget,
+    /// Resolving the link runs into a cycle of links.
+    Loop,
+}
+
+impl BrokenReason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrokenReason::MissingTarget => "Missing target",
+            BrokenReason::Loop => "Symlink loop",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BrokenSymlink {
+    pub path: PathBuf,
+    pub target: PathBuf,
+    pub reason: BrokenReason,
+}
+
+#[cfg(unix)]
+fn is_loop(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(libc::ELOOP)
+}
+
+#[cfg(not(unix))]
+fn is_loop(_err: &io::Error) -> bool {
+    false
+}
+
+/// Checks whether the symbolic link at `path` resolves. Only the link itself
+/// is inspected; nothing it points to is ever opened or walked.
+pub fn check_symlink(path: &Path) -> Option<BrokenSymlink> {
+    let target = fs::read_link(path).ok()?;
+    let reason = match fs::metadata(path) {
+        Ok(_) => return None,
+        Err(e) if is_loop(&e) => BrokenReason::Loop,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => BrokenReason::MissingTarget,
+        // A target we may not look at is not known to be missing.
+        Err(_) => return None,
+    };
+    Some(BrokenSymlink { path: path.to_path_buf(), target, reason })
+}
+
+/// Finds the symbolic links under `dirs_to_scan` whose target no longer
+/// exists or loops back on itself. Links are never followed, so the walk
+/// stays inside the scanned directories.
+pub fn find_broken_symlinks<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>) -> Vec<BrokenSymlink> {
+    let mut broken = Vec::new();
+
+    for dir in dirs_to_scan {
+        let dir = dir.as_ref();
+        if exclude_dirs.contains(dir.to_str().unwrap_or_default()) || !has_permission(dir) {
+            continue;
+        }
+        let walker = mounts::walk_filtered(WalkDir::new(dir).follow_links(false), |e| {
+            !exclude_dirs.contains(e.path().to_str().unwrap_or_default())
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if entry.path_is_symlink() {
+                        if let Some(link) = check_symlink(entry.path()) {
+                            safe_delete::record_entry(&entry);
+                            broken.push(link);
+                        }
+                    }
+                }
+                Err(e) => {
+                    if is_permission_denied(&e) {
+                        eprintln!("{}", format!("Permission denied: {}", e).red());
+                    } else {
+                        eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                    }
+                }
+            }
+        }
+    }
+
+    broken.sort_by(|a, b| a.path.cmp(&b.path));
+    broken
+}
+
+pub fn print_broken_symlinks_report(broken: &[BrokenSymlink]) {
+    let mut table = Table::new();
+    table.add_row(row!["Link Path".bold().blue(), "Target".bold().blue(), "Status".bold().blue()]);
+    for link in broken {
+        table.add_row(Row::new(vec![
+            Cell::new(&link.path.display().to_string().blue().to_string()),
+            Cell::new(&link.target.display().to_string()),
+            Cell::new(&link.reason.name().yellow().to_string()),
+        ]));
+    }
+    table.printstd();
+    println!("{}", format!("Broken symbolic links: {}", broken.len()).green());
+}
+
+// Tests Unitaires
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_broken_symlinks() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        fs::write(dir.path().join("real.txt"), "data").unwrap();
+        symlink(dir.path().join("real.txt"), dir.path().join("ok")).unwrap();
+        symlink(dir.path().join("gone.txt"), dir.path().join("dangling")).unwrap();
+        symlink(dir.path().join("loop-b"), dir.path().join("loop-a")).unwrap();
+        symlink(dir.path().join("loop-a"), dir.path().join("loop-b")).unwrap();
+        // A link to a directory outside the root must not be walked into.
+        fs::create_dir(outside.path().join("sub")).unwrap();
+        symlink(outside.path().join("missing"), outside.path().join("sub").join("dangling")).unwrap();
+        symlink(outside.path(), dir.path().join("outside")).unwrap();
+
+        let broken = find_broken_symlinks(&[dir.path()], &HashSet::new());
+
+        let found: Vec<(String, BrokenReason)> = broken.iter()
+            .map(|b| (b.path.file_name().unwrap().to_string_lossy().into_owned(), b.reason))
+            .collect();
+        assert_eq!(found, vec![
+            ("dangling".to_string(), BrokenReason::MissingTarget),
+            ("loop-a".to_string(), BrokenReason::Loop),
+            ("loop-b".to_string(), BrokenReason::Loop),
+        ]);
+        assert_eq!(broken[0].target, dir.path().join("gone.txt"));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::cleaner::deletion_failure;
+use crate::mounts;
+use crate::safe_delete;
+use crate::usage::human_size;
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A system package manager that keeps downloaded packages in a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Manager {
+    Apt,
+    Dnf,
+    Pacman,
+}
+
+impl Manager {
+    pub const ALL: [Manager; 3] = [Manager::Apt, Manager::Dnf, Manager::Pacman];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Manager::Apt => "apt",
+            Manager::Dnf => "dnf",
+            Manager::Pacman => "pacman",
+        }
+    }
+
+    pub fn cache_dir(&self) -> &'static Path {
+        Path::new(match self {
+            Manager::Apt => "/var/cache/apt/archives",
+            Manager::Dnf => "/var/cache/dnf",
+            Manager::Pacman => "/var/cache/pacman/pkg",
+        })
+    }
+
+    /// The locks the manager holds while it installs or downloads packages.
+    fn locks(&self) -> &'static [(LockKind, &'static str)] {
+        match self {
+            Manager::Apt => &[
+                (LockKind::Fcntl, "/var/lib/dpkg/lock-frontend"),
+                (LockKind::Fcntl, "/var/lib/dpkg/lock"),
+                (LockKind::Fcntl, "/var/cache/apt/archives/lock"),
+            ],
+            Manager::Dnf => &[
+                (LockKind::PidFile, "/run/dnf.rpmdb.lock"),
+                (LockKind::PidFile, "/run/dnf.librepo.lock"),
+                (LockKind::PidFile, "/var/cache/dnf/metadata_lock.pid"),
+                (LockKind::Fcntl, "/var/lib/rpm/.rpm.lock"),
+                (LockKind::Fcntl, "/usr/lib/sysimage/rpm/.rpm.lock"),
+            ],
+            Manager::Pacman => &[(LockKind::Exists, "/var/lib/pacman/db.lck")],
+        }
+    }
+
+    /// The first lock of the manager that is held, if it is running.
+    pub fn held_lock(&self) -> Option<&'static Path> {
+        self.locks().iter().map(|(kind, path)| (kind, Path::new(*path))).find(|(kind, path)| is_held(**kind, path)).map(|(_, path)| path)
+    }
+
+    /// Splits a package file name into its name, version and architecture:
+    /// `name_version_arch.deb` with the epoch colon escaped as `%3a` for apt,
+    /// `name-version-release.arch.rpm` for dnf and
+    /// `name-version-release-arch.pkg.tar.zst` for pacman.
+    pub fn parse_file_name(&self, file_name: &str) -> Option<(String, String, String)> {
+        match self {
+            Manager::Apt => {
+                let mut parts = file_name.strip_suffix(".deb")?.split('_');
+                let (name, version, arch) = (parts.next()?, parts.next()?, parts.next()?);
+                if parts.next().is_some() {
+                    return None;
+                }
+                let version = version.replace("%3a", ":").replace("%3A", ":");
+                Some((name.to_string(), version, arch.to_string()))
+            }
+            Manager::Dnf => {
+                let (rest, arch) = file_name.strip_suffix(".rpm")?.rsplit_once('.')?;
+                let (rest, release) = rest.rsplit_once('-')?;
+                let (name, version) = rest.rsplit_once('-')?;
+                Some((name.to_string(), format!("{}-{}", version, release), arch.to_string()))
+            }
+            Manager::Pacman => {
+                let (stem, compression) = file_name.split_once(".pkg.tar")?;
+                if !["", ".gz", ".bz2", ".xz", ".zst", ".lz4", ".lzo", ".lz", ".lrz", ".Z"].contains(&compression) {
+                    return None;
+                }
+                let (rest, arch) = stem.rsplit_once('-')?;
+                let (rest, release) = rest.rsplit_once('-')?;
+                let (name, version) = rest.rsplit_once('-')?;
+                Some((name.to_string(), format!("{}-{}", version, release), arch.to_string()))
+            }
+        }
+    }
+}
+
+/// How a package manager marks that it is running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockKind {
+    /// A file that always exists, locked with `fcntl` (dpkg, apt, rpm).
+    Fcntl,
+    /// A file holding the PID of the running process (dnf).
+    PidFile,
+    /// A file that only exists while the manager runs (pacman).
+    Exists,
+}
+
+/// Whether the lock at `path` is held. A lock that cannot be checked, such as
+/// one we may not open, is taken as held.
+fn is_held(kind: LockKind, path: &Path) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    match kind {
+        LockKind::Exists => true,
+        LockKind::PidFile => match std::fs::read_to_string(path) {
+            Ok(text) => text.trim().parse::<u32>().map_or(true, |pid| Path::new("/proc").join(pid.to_string()).exists()),
+            Err(_) => true,
+        },
+        LockKind::Fcntl => fcntl_locked(path),
+    }
+}
+
+#[cfg(unix)]
+fn fcntl_locked(path: &Path) -> bool {
+    use std::os::fd::AsRawFd;
+    let Ok(file) = std::fs::File::open(path) else { return true };
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) } != 0 {
+        return true;
+    }
+    lock.l_type as libc::c_int != libc::F_UNLCK
+}
+
+#[cfg(not(unix))]
+fn fcntl_locked(_path: &Path) -> bool {
+    false
+}
+
+/// A package file in a cache, with its detached signature if it has one.
+#[derive(Debug, Clone)]
+pub struct PackageFile {
+    pub manager: Manager,
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    pub path: PathBuf,
+    pub signature: Option<PathBuf>,
+    /// Size of the package and of its signature.
+    pub size: u64,
+}
+
+/// Compares two package versions the way rpm does, which pacman and dpkg
+/// follow outside of corner cases: the epoch first, then runs of digits
+/// numerically and runs of letters alphabetically, with a `~` sorting before
+/// anything, even the end of the version.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split_epoch = |v: &str| match v.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => (epoch.parse::<u64>().unwrap_or(0), rest.to_string()),
+        _ => (0, v.to_string()),
+    };
+    let (epoch_a, a) = split_epoch(a);
+    let (epoch_b, b) = split_epoch(b);
+    epoch_a.cmp(&epoch_b).then_with(|| compare_segments(&a, &b))
+}
+
+fn compare_segments(a: &str, b: &str) -> Ordering {
+    let is_separator = |c: char| !c.is_ascii_alphanumeric() && c != '~';
+    let (mut a, mut b) = (a, b);
+    loop {
+        a = a.trim_start_matches(is_separator);
+        b = b.trim_start_matches(is_separator);
+        match (a.strip_prefix('~'), b.strip_prefix('~')) {
+            (Some(rest_a), Some(rest_b)) => {
+                a = rest_a;
+                b = rest_b;
+                continue;
+            }
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => {}
+        }
+        if a.is_empty() || b.is_empty() {
+            return a.len().cmp(&b.len());
+        }
+
+        let numeric = a.starts_with(|c: char| c.is_ascii_digit());
+        let in_segment = |c: char| if numeric { c.is_ascii_digit() } else { c.is_ascii_alphabetic() };
+        let end_a = a.find(|c| !in_segment(c)).unwrap_or(a.len());
+        let end_b = b.find(|c| !in_segment(c)).unwrap_or(b.len());
+        let (segment_a, segment_b) = (&a[..end_a], &b[..end_b]);
+        if segment_b.is_empty() {
+            // A number is newer than letters.
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+        let order = if numeric {
+            let (x, y) = (segment_a.trim_start_matches('0'), segment_b.trim_start_matches('0'));
+            x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+        } else {
+            segment_a.cmp(segment_b)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+        a = &a[end_a..];
+        b = &b[end_b..];
+    }
+}
+
+/// Finds the package files in the cache of `manager` under `cache_dir`.
+/// Partial downloads and files that are not packages are left out.
+pub fn find_packages(manager: Manager, cache_dir: &Path) -> Vec<PackageFile> {
+    let mut packages = Vec::new();
+    let walker = mounts::walk_filtered(WalkDir::new(cache_dir).min_depth(1), |e| e.file_name() != "partial");
+    for entry in walker.flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy();
+        if file_name.ends_with(".sig") {
+            continue;
+        }
+        let Some((name, version, arch)) = manager.parse_file_name(&file_name) else { continue };
+        let Ok(metadata) = entry.metadata() else { continue };
+        safe_delete::record_walked(&entry, &metadata);
+
+        let signature = entry.path().with_file_name(format!("{}.sig", file_name));
+        let signature = match signature.symlink_metadata() {
+            Ok(sig) if sig.is_file() => {
+                safe_delete::record(&signature, &sig);
+                Some((signature, sig.len()))
+            }
+            _ => None,
+        };
+        packages.push(PackageFile {
+            manager,
+            name,
+            version,
+            arch,
+            path: entry.path().to_path_buf(),
+            size: metadata.len() + signature.as_ref().map_or(0, |(_, len)| *len),
+            signature: signature.map(|(path, _)| path),
+        });
+    }
+    packages
+}
+
+/// The packages to remove so that only the `keep` newest versions of each
+/// package and architecture remain, like `paccache -rk`.
+pub fn old_versions(packages: &[PackageFile], keep: usize) -> Vec<PackageFile> {
+    let mut groups: BTreeMap<(Manager, &str, &str), Vec<&PackageFile>> = BTreeMap::new();
+    for package in packages {
+        groups.entry((package.manager, &package.name, &package.arch)).or_default().push(package);
+    }
+    let mut old = Vec::new();
+    for versions in groups.values_mut() {
+        versions.sort_by(|a, b| compare_versions(&b.version, &a.version).then_with(|| a.path.cmp(&b.path)));
+        old.extend(versions.iter().skip(keep).map(|&p| p.clone()));
+    }
+    old
+}
+
+/// Whether the process runs as root, which the system caches require.
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+pub fn print_old_versions_report(old: &[PackageFile]) {
+    let mut table = Table::new();
+    table.add_row(row!["Manager".bold().blue(), "Package".bold().blue(), "Version".bold().blue(), "File".bold().blue(), "Size".bold().blue()]);
+    for package in old {
+        table.add_row(Row::new(vec![
+            Cell::new(package.manager.name()),
+            Cell::new(&format!("{} ({})", package.name, package.arch)),
+            Cell::new(&package.version),
+            Cell::new(&package.path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(package.size)),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Prints, per package manager, the size of its cache and how much of it
+/// the old versions take.
+pub fn print_reclaimable_report(packages: &[PackageFile], old: &[PackageFile]) {
+    let mut table = Table::new();
+    table.add_row(row!["Manager".bold().blue(), "Cache".bold().blue(), "Packages".bold().blue(), "Size".bold().blue(), "Old Versions".bold().blue(), "Reclaimable".bold().blue()]);
+    for manager in Manager::ALL {
+        let all: Vec<&PackageFile> = packages.iter().filter(|p| p.manager == manager).collect();
+        if all.is_empty() {
+            continue;
+        }
+        let removable: Vec<&PackageFile> = old.iter().filter(|p| p.manager == manager).collect();
+        table.add_row(Row::new(vec![
+            Cell::new(manager.name()),
+            Cell::new(&manager.cache_dir().display().to_string().blue().to_string()),
+            Cell::new(&all.len().to_string()),
+            Cell::new(&human_size(all.iter().map(|p| p.size).sum())),
+            Cell::new(&removable.len().to_string()),
+            Cell::new(&human_size(removable.iter().map(|p| p.size).sum()).green().to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Deletes `packages` and their signatures and returns the space freed. The
+/// packages of a manager that is running are left alone, as it may be
+/// installing them.
+pub fn remove_packages(packages: &[PackageFile]) -> u64 {
+    let mut table = Table::new();
+    table.add_row(row!["File".bold().blue(), "Status".bold().blue()]);
+    let mut freed = 0;
+
+    let mut running = BTreeSet::new();
+    for manager in Manager::ALL {
+        if let Some(lock) = packages.iter().any(|p| p.manager == manager).then(|| manager.held_lock()).flatten() {
+            eprintln!("{}", format!("{} is running ({} is locked), its packages are left alone", manager.name(), lock.display()).yellow());
+            running.insert(manager);
+        }
+    }
+
+    for package in packages {
+        if running.contains(&package.manager) {
+            table.add_row(Row::new(vec![
+                Cell::new(&package.path.display().to_string().blue().to_string()),
+                Cell::new(&format!("{} running", package.manager.name()).yellow().to_string()),
+            ]));
+            continue;
+        }
+        let result = safe_delete::remove_file(&package.path)
+            .and_then(|_| package.signature.as_deref().map_or(Ok(()), safe_delete::remove_file));
+        let status = match result {
+            Ok(_) => {
+                freed += package.size;
+                "Deleted".to_string()
+            }
+            Err(e) => deletion_failure(&package.path, &e),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&package.path.display().to_string().blue().to_string()),
+            Cell::new(&status.green().to_string()),
+        ]));
+    }
+
+    table.printstd();
+    println!("{}", format!("Space freed: {}", human_size(freed)).green());
+    freed
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_file_names_and_compare_versions() {
+        let parsed = |manager: Manager, name: &str| manager.parse_file_name(name);
+        let owned = |n: &str, v: &str, a: &str| Some((n.to_string(), v.to_string(), a.to_string()));
+        assert_eq!(parsed(Manager::Apt, "libc6_2.36-9+deb12u4_amd64.deb"), owned("libc6", "2.36-9+deb12u4", "amd64"));
+        assert_eq!(parsed(Manager::Apt, "vim_2%3a9.0.1378-2_amd64.deb"), owned("vim", "2:9.0.1378-2", "amd64"));
+        assert_eq!(parsed(Manager::Dnf, "kernel-core-6.8.5-301.fc40.x86_64.rpm"), owned("kernel-core", "6.8.5-301.fc40", "x86_64"));
+        assert_eq!(parsed(Manager::Pacman, "linux-firmware-20240409.1addd7dc-1-any.pkg.tar.zst"), owned("linux-firmware", "20240409.1addd7dc-1", "any"));
+        assert_eq!(parsed(Manager::Pacman, "python-3.12.3-1-x86_64.pkg.tar.xz"), owned("python", "3.12.3-1", "x86_64"));
+        assert_eq!(parsed(Manager::Apt, "lock"), None);
+        assert_eq!(parsed(Manager::Pacman, "python-3.12.3-1-x86_64.pkg.tar.zst.part"), None);
+
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1:1.0", "2.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0~rc1", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0a", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("2.36-9+deb12u4", "2.36-9+deb12u10"), Ordering::Less);
+        assert_eq!(compare_versions("1.01", "1.1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_keep_newest_versions() {
+        let dir = tempdir().unwrap();
+        for (file, size) in [
+            ("python-3.12.3-1-x86_64.pkg.tar.zst", 100),
+            ("python-3.12.10-1-x86_64.pkg.tar.zst", 100),
+            ("python-3.9.1-2-x86_64.pkg.tar.zst", 100),
+            ("python-3.9.1-2-x86_64.pkg.tar.zst.sig", 10),
+            ("zlib-1:1.3.1-1-x86_64.pkg.tar.zst", 50),
+            ("notes.txt", 5),
+        ] {
+            fs::write(dir.path().join(file), vec![0u8; size]).unwrap();
+        }
+
+        let packages = find_packages(Manager::Pacman, dir.path());
+        assert_eq!(packages.len(), 4);
+        let old = old_versions(&packages, 2);
+        assert_eq!(old.len(), 1);
+        assert_eq!(old[0].version, "3.9.1-2");
+        assert_eq!(old[0].size, 110);
+        assert_eq!(old_versions(&packages, 1).len(), 2);
+
+        assert_eq!(remove_packages(&old), 110);
+        assert!(!dir.path().join("python-3.9.1-2-x86_64.pkg.tar.zst.sig").exists());
+        assert!(dir.path().join("python-3.12.3-1-x86_64.pkg.tar.zst").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_package_manager_locks() {
+        let dir = tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        assert!(!is_held(LockKind::Exists, &lock));
+        fs::write(&lock, "").unwrap();
+        assert!(is_held(LockKind::Exists, &lock));
+        // dpkg and rpm lock files stay behind when nobody holds them.
+        assert!(!is_held(LockKind::Fcntl, &lock));
+
+        fs::write(&lock, std::process::id().to_string()).unwrap();
+        assert!(is_held(LockKind::PidFile, &lock));
+        fs::write(&lock, "4294967").unwrap();
+        assert!(!is_held(LockKind::PidFile, &lock));
+    }
+}
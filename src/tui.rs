@@ -0,0 +1,405 @@
+use crate::cleaner::{has_permission, is_cleanable};
+use crate::mounts;
+use crate::safe_delete;
+use crate::usage::human_size;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Apparent size, including everything below a directory.
+    pub size: u64,
+    /// Files selected by the cleaning rules, including everything below.
+    pub matches: u64,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub marked: bool,
+}
+
+/// In-memory copy of the scanned directories. Node 0 is a virtual root whose
+/// children are the scanned directories.
+#[derive(Debug)]
+pub struct Tree {
+    nodes: Vec<Node>,
+}
+
+/// What the marked entries add up to.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Selection {
+    /// The marked entries themselves, without those inside a marked folder.
+    pub items: Vec<PathBuf>,
+    /// Every file in the selection, with the content of marked folders.
+    pub files: Vec<PathBuf>,
+    /// The marked folders.
+    pub dirs: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Delete,
+    SecureDelete,
+    Trash,
+}
+
+impl Action {
+    fn verb(&self) -> &'static str {
+        match self {
+            Action::Delete => "Delete",
+            Action::SecureDelete => "Securely delete",
+            Action::Trash => "Move to trash",
+        }
+    }
+}
+
+impl Tree {
+    pub fn build<P: AsRef<Path>>(roots: &[P], exclude_dirs: &HashSet<String>, exclude_types: &HashSet<String>) -> Tree {
+        let mut nodes = vec![Node {
+            name: String::new(),
+            path: PathBuf::new(),
+            is_dir: true,
+            size: 0,
+            matches: 0,
+            parent: None,
+            children: Vec::new(),
+            marked: false,
+        }];
+        let mut dir_index: HashMap<PathBuf, usize> = HashMap::new();
+
+        for root in roots {
+            let root = root.as_ref();
+            if exclude_dirs.contains(root.to_str().unwrap_or_default()) || !has_permission(root) {
+                continue;
+            }
+            let walker = mounts::walk_filtered(WalkDir::new(root), |e| {
+                !exclude_dirs.contains(e.path().to_str().unwrap_or_default())
+            });
+            for entry in walker.flatten() {
+                let parent = if entry.depth() == 0 {
+                    ROOT
+                } else {
+                    match entry.path().parent().and_then(|p| dir_index.get(p)) {
+                        Some(&index) => index,
+                        None => continue,
+                    }
+                };
+                let is_dir = entry.file_type().is_dir();
+                let metadata = entry.metadata().ok();
+                if let Some(metadata) = &metadata {
+                    safe_delete::record_walked(&entry, metadata);
+                }
+                let is_file = !is_dir;
+                let name = if entry.depth() == 0 {
+                    entry.path().display().to_string()
+                } else {
+                    entry.file_name().to_string_lossy().into_owned()
+                };
+                let index = nodes.len();
+                nodes.push(Node {
+                    name,
+                    path: entry.path().to_path_buf(),
+                    is_dir,
+                    size: if is_file { metadata.map_or(0, |m| m.len()) } else { 0 },
+                    matches: u64::from(is_file && is_cleanable(entry.path(), exclude_types)),
+                    parent: Some(parent),
+                    children: Vec::new(),
+                    marked: false,
+                });
+                nodes[parent].children.push(index);
+                if is_dir {
+                    dir_index.insert(entry.path().to_path_buf(), index);
+                }
+            }
+        }
+
+        // Children always come after their parent, so one backwards pass
+        // adds every subtree into its parent.
+        for index in (1..nodes.len()).rev() {
+            let (size, matches) = (nodes[index].size, nodes[index].matches);
+            let parent = nodes[index].parent.unwrap();
+            nodes[parent].size += size;
+            nodes[parent].matches += matches;
+        }
+        Tree { nodes }
+    }
+
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    /// Children of `index`, largest first.
+    pub fn sorted_children(&self, index: usize) -> Vec<usize> {
+        let mut children = self.nodes[index].children.clone();
+        children.sort_by(|&a, &b| {
+            self.nodes[b].size.cmp(&self.nodes[a].size).then_with(|| self.nodes[a].name.cmp(&self.nodes[b].name))
+        });
+        children
+    }
+
+    pub fn toggle_mark(&mut self, index: usize) {
+        if index != ROOT {
+            self.nodes[index].marked = !self.nodes[index].marked;
+        }
+    }
+
+    /// Marks every file below `index` that the cleaning rules select.
+    pub fn mark_matches(&mut self, index: usize) {
+        let mut stack = vec![index];
+        while let Some(current) = stack.pop() {
+            let node = &mut self.nodes[current];
+            if !node.is_dir && node.matches > 0 {
+                node.marked = true;
+            }
+            stack.extend(node.children.iter().copied());
+        }
+    }
+
+    fn collect_files(&self, index: usize, files: &mut Vec<PathBuf>) {
+        let node = &self.nodes[index];
+        if node.is_dir {
+            for &child in &node.children {
+                self.collect_files(child, files);
+            }
+        } else {
+            files.push(node.path.clone());
+        }
+    }
+
+    pub fn selection(&self) -> Selection {
+        let mut selection = Selection::default();
+        let mut stack = vec![ROOT];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.marked {
+                selection.items.push(node.path.clone());
+                selection.bytes += node.size;
+                if node.is_dir {
+                    selection.dirs.push(node.path.clone());
+                }
+                self.collect_files(index, &mut selection.files);
+            } else {
+                stack.extend(node.children.iter().copied());
+            }
+        }
+        selection.items.sort();
+        selection.files.sort();
+        selection.dirs.sort();
+        selection
+    }
+}
+
+struct App {
+    tree: Tree,
+    current: usize,
+    state: ListState,
+    confirm: Option<Action>,
+}
+
+impl App {
+    fn visible(&self) -> Vec<usize> {
+        self.tree.sorted_children(self.current)
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.visible().get(self.state.selected()?).copied()
+    }
+
+    fn open(&mut self, index: usize) {
+        if self.tree.node(index).is_dir {
+            self.current = index;
+            self.state.select(Some(0));
+        }
+    }
+
+    fn up(&mut self) {
+        if let Some(parent) = self.tree.node(self.current).parent {
+            let from = self.current;
+            self.current = parent;
+            let position = self.visible().iter().position(|&i| i == from);
+            self.state.select(position.or(Some(0)));
+        }
+    }
+}
+
+pub(crate) fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)]).flex(Flex::Center).areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center).areas(area);
+    area
+}
+
+fn bar(size: u64, largest: u64) -> String {
+    const WIDTH: u64 = 10;
+    let filled = if largest == 0 { 0 } else { (size * WIDTH).div_ceil(largest) } as usize;
+    format!("[{}{}]", "#".repeat(filled), " ".repeat(WIDTH as usize - filled))
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, footer] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let current = app.tree.node(app.current);
+    let title = if app.current == ROOT { "Scanned directories".to_string() } else { current.path.display().to_string() };
+    frame.render_widget(
+        Line::from(format!(" {}  ({})", title, human_size(current.size))).style(Style::new().bold().reversed()),
+        header,
+    );
+
+    let children = app.visible();
+    let largest = children.first().map_or(0, |&i| app.tree.node(i).size);
+    let items: Vec<ListItem> = children
+        .iter()
+        .map(|&index| {
+            let node = app.tree.node(index);
+            let mut spans = vec![
+                Span::raw(if node.marked { "[x] " } else { "[ ] " }),
+                Span::raw(format!("{:>10} ", human_size(node.size))),
+                Span::raw(format!("{} ", bar(node.size, largest))),
+            ];
+            let name = if node.is_dir { format!("{}/", node.name) } else { node.name.clone() };
+            let mut name_style = Style::new();
+            if node.matches > 0 {
+                name_style = name_style.fg(Color::Yellow);
+            }
+            if node.marked {
+                name_style = name_style.add_modifier(Modifier::BOLD).fg(Color::Green);
+            }
+            spans.push(Span::styled(name, name_style));
+            if node.is_dir && node.matches > 0 {
+                spans.push(Span::styled(format!("  ({} to clean)", node.matches), Style::new().fg(Color::Yellow)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(Style::new().reversed());
+    frame.render_stateful_widget(list, body, &mut app.state);
+
+    let selection = app.tree.selection();
+    frame.render_widget(
+        Line::from(format!(
+            " Marked: {} ({}) | space mark  c mark rule matches  enter open  backspace up  d delete  s secure delete  t trash  q quit",
+            selection.items.len(),
+            human_size(selection.bytes)
+        )).style(Style::new().reversed()),
+        footer,
+    );
+
+    if let Some(action) = app.confirm {
+        let text = vec![
+            Line::from(format!("{} {} files in {} marked entries?", action.verb(), selection.files.len(), selection.items.len())),
+            Line::from(format!("Total size: {}", human_size(selection.bytes))),
+            Line::from(""),
+            Line::from("y = confirm, any other key = cancel"),
+        ];
+        let area = centered(frame.area(), 60, 6);
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(text).block(Block::bordered().title(" Confirm ")), area);
+    }
+}
+
+fn run_app(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<Option<Action>> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some(action) = app.confirm.take() {
+            if key.code == KeyCode::Char('y') {
+                return Ok(Some(action));
+            }
+            continue;
+        }
+        let count = app.visible().len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+                app.state.select(Some((app.state.selected().unwrap_or(0) + 1).min(count - 1)));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.state.select(Some(app.state.selected().unwrap_or(0).saturating_sub(1)));
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(index) = app.selected() {
+                    app.open(index);
+                }
+            }
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => app.up(),
+            KeyCode::Char(' ') => {
+                if let Some(index) = app.selected() {
+                    app.tree.toggle_mark(index);
+                    app.state.select(Some((app.state.selected().unwrap_or(0) + 1).min(count.saturating_sub(1))));
+                }
+            }
+            KeyCode::Char('c') => app.tree.mark_matches(app.current),
+            KeyCode::Char('d') | KeyCode::Char('s') | KeyCode::Char('t') if !app.tree.selection().items.is_empty() => {
+                app.confirm = Some(match key.code {
+                    KeyCode::Char('d') => Action::Delete,
+                    KeyCode::Char('s') => Action::SecureDelete,
+                    _ => Action::Trash,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Opens the explorer on `tree` and returns the confirmed action with the
+/// marked entries, or `None` when the user quits.
+pub fn explore(tree: Tree) -> io::Result<Option<(Action, Selection)>> {
+    let mut app = App { tree, current: ROOT, state: ListState::default().with_selected(Some(0)), confirm: None };
+    // A single scanned directory is opened directly.
+    if let [only] = app.tree.node(ROOT).children[..] {
+        app.open(only);
+    }
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, &mut app);
+    ratatui::restore();
+    Ok(result?.map(|action| (action, app.tree.selection())))
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tree_sizes_marks_and_selection() {
+        let dir = tempdir().unwrap();
+        let logs = dir.path().join("logs");
+        fs::create_dir_all(&logs).unwrap();
+        fs::write(logs.join("a.log"), vec![0u8; 300]).unwrap();
+        fs::write(logs.join("notes.txt"), vec![0u8; 100]).unwrap();
+        fs::write(dir.path().join("big.iso"), vec![0u8; 1000]).unwrap();
+        fs::write(dir.path().join("b.tmp"), vec![0u8; 50]).unwrap();
+
+        let mut tree = Tree::build(&[dir.path()], &HashSet::new(), &HashSet::new());
+        let root = tree.node(ROOT).children[0];
+        assert_eq!(tree.node(root).size, 1450);
+        assert_eq!(tree.node(root).matches, 2);
+
+        let names: Vec<&str> = tree.sorted_children(root).iter().map(|&i| tree.node(i).name.as_str()).collect();
+        assert_eq!(names, vec!["big.iso", "logs", "b.tmp"]);
+
+        let logs_node = tree.sorted_children(root)[1];
+        tree.toggle_mark(logs_node);
+        tree.mark_matches(root);
+        let selection = tree.selection();
+        assert_eq!(selection.items, vec![dir.path().join("b.tmp"), logs.clone()]);
+        assert_eq!(selection.dirs, vec![logs.clone()]);
+        assert_eq!(selection.files, vec![dir.path().join("b.tmp"), logs.join("a.log"), logs.join("notes.txt")]);
+        assert_eq!(selection.bytes, 450);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::age::AgeRule;
+use crate::cleaner::{has_permission, is_permission_denied};
+use crate::filter;
+use crate::mounts;
+use crate::safe_delete;
+use crate::size;
+use chrono::{DateTime, Utc};
+use colored::*;
+use prettytable::{Table, Row, Cell, row};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Which files are counted and how many entries are listed.
+#[derive(Debug, Clone)]
+pub struct UsageOptions {
+    pub top: usize,
+    /// Only count files with one of these extensions; empty counts all.
+    pub extensions: HashSet<String>,
+    /// Only count files in this age range.
+    pub age: Option<AgeRule>,
+    /// Rank by disk space actually allocated instead of apparent size.
+    pub by_allocated: bool,
+}
+
+impl Default for UsageOptions {
+    fn default() -> Self {
+        UsageOptions { top: 20, extensions: HashSet::new(), age: None, by_allocated: false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileUsage {
+    pub path: PathBuf,
+    pub apparent: u64,
+    pub allocated: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DirUsage {
+    pub path: PathBuf,
+    pub apparent: u64,
+    pub allocated: u64,
+    pub files: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct UsageReport {
+    pub files: Vec<FileUsage>,
+    pub dirs: Vec<DirUsage>,
+}
+
+/// Formats a byte count with binary units, e.g. "1.5 GiB".
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always counted in 512-byte units.
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn inode_of(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode_of(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+impl UsageOptions {
+    fn matches(&self, path: &Path, metadata: &fs::Metadata, now: DateTime<Utc>) -> bool {
+        if !self.extensions.is_empty() {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+            if !self.extensions.contains(&ext) {
+                return false;
+            }
+        }
+        self.age.as_ref().is_none_or(|rule| rule.matches(metadata, now)) && size::accepts(metadata) && filter::accepts(path, metadata)
+    }
+}
+
+/// Walks `dirs_to_scan` and calls `visit` with each regular file that passes
+/// the filters of `options`.
+fn walk_matching_files<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>, options: &UsageOptions, mut visit: impl FnMut(&DirEntry, &fs::Metadata)) {
+    let now = Utc::now();
+    for dir in dirs_to_scan {
+        let dir = dir.as_ref();
+        if exclude_dirs.contains(dir.to_str().unwrap_or_default()) || !has_permission(dir) {
+            continue;
+        }
+        let walker = mounts::walk_filtered(WalkDir::new(dir), |e| {
+            !exclude_dirs.contains(e.path().to_str().unwrap_or_default())
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if !entry.file_type().is_file() {
+                        continue;
+                    }
+                    if let Ok(metadata) = entry.metadata() {
+                        if options.matches(entry.path(), &metadata, now) {
+                            visit(&entry, &metadata);
+                        }
+                    }
+                }
+                Err(e) => {
+                    if is_permission_denied(&e) {
+                        eprintln!("{}", format!("Permission denied: {}", e).red());
+                    } else {
+                        eprintln!("{}", format!("Failed to access entry: {}", e).red());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finds the largest files and the directories with the largest cumulative
+/// size under `dirs_to_scan`. A file with several hard links is only counted
+/// once in directory totals.
+pub fn analyze<P: AsRef<Path>>(dirs_to_scan: &[P], exclude_dirs: &HashSet<String>, options: &UsageOptions) -> UsageReport {
+    let mut files = Vec::new();
+    let mut dirs: HashMap<PathBuf, DirUsage> = HashMap::new();
+    let mut seen_inodes = HashSet::new();
+
+    for root in dirs_to_scan {
+        let root = root.as_ref();
+        walk_matching_files(&[root], exclude_dirs, options, |entry, metadata| {
+            let apparent = metadata.len();
+            let allocated = allocated_size(metadata);
+            files.push((FileUsage {
+                path: entry.path().to_path_buf(),
+                apparent,
+                allocated,
+                modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            }, metadata.clone()));
+            if inode_of(metadata).is_some_and(|inode| !seen_inodes.insert(inode)) {
+                return;
+            }
+            for ancestor in entry.path().ancestors().skip(1) {
+                let usage = dirs.entry(ancestor.to_path_buf()).or_insert_with(|| DirUsage { path: ancestor.to_path_buf(), ..Default::default() });
+                usage.apparent += apparent;
+                usage.allocated += allocated;
+                usage.files += 1;
+                if ancestor == root {
+                    break;
+                }
+            }
+        });
+    }
+
+    let key = |apparent: u64, allocated: u64| if options.by_allocated { allocated } else { apparent };
+    files.sort_by(|(a, _), (b, _)| key(b.apparent, b.allocated).cmp(&key(a.apparent, a.allocated)).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(options.top);
+    // The listed files can be sent to the cleaner, so they are recorded like
+    // any other scan result.
+    let files = files.into_iter()
+        .map(|(file, metadata)| {
+            safe_delete::record(&file.path, &metadata);
+            file
+        })
+        .collect();
+    let mut dirs: Vec<DirUsage> = dirs.into_values().collect();
+    dirs.sort_by(|a, b| key(b.apparent, b.allocated).cmp(&key(a.apparent, a.allocated)).then_with(|| a.path.cmp(&b.path)));
+    dirs.truncate(options.top);
+    UsageReport { files, dirs }
+}
+
+/// Lists the files under `dir` that pass the filters of `options`, recorded
+/// for deletion like any other scan result.
+pub fn matching_files(dir: &Path, exclude_dirs: &HashSet<String>, options: &UsageOptions) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk_matching_files(&[dir], exclude_dirs, options, |entry, metadata| {
+        safe_delete::record_walked(entry, metadata);
+        files.push(entry.path().to_path_buf());
+    });
+    files
+}
+
+pub fn print_usage_report(report: &UsageReport) {
+    println!("{}", "Largest files".bold());
+    let mut table = Table::new();
+    table.add_row(row!["#".bold().blue(), "File Path".bold().blue(), "Size".bold().blue(), "On Disk".bold().blue(), "Modified".bold().blue()]);
+    for (index, file) in report.files.iter().enumerate() {
+        table.add_row(Row::new(vec![
+            Cell::new(&(index + 1).to_string()),
+            Cell::new(&file.path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(file.apparent)),
+            Cell::new(&human_size(file.allocated)),
+            Cell::new(&file.modified.map(|m| m.format("%Y-%m-%d").to_string()).unwrap_or_default()),
+        ]));
+    }
+    table.printstd();
+
+    println!("{}", "Largest directories".bold());
+    let mut table = Table::new();
+    table.add_row(row!["#".bold().blue(), "Directory".bold().blue(), "Size".bold().blue(), "On Disk".bold().blue(), "Files".bold().blue()]);
+    for (index, dir) in report.dirs.iter().enumerate() {
+        table.add_row(Row::new(vec![
+            Cell::new(&(index + 1).to_string()),
+            Cell::new(&dir.path.display().to_string().blue().to_string()),
+            Cell::new(&human_size(dir.apparent)),
+            Cell::new(&human_size(dir.allocated)),
+            Cell::new(&dir.files.to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+// Tests Unitaires
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_analyze_ranks_files_and_directories() {
+        let dir = tempdir().unwrap();
+        let big = dir.path().join("videos");
+        let small = dir.path().join("logs");
+        fs::create_dir_all(big.join("2023")).unwrap();
+        fs::create_dir_all(&small).unwrap();
+        fs::write(big.join("2023").join("a.mp4"), vec![0u8; 5000]).unwrap();
+        fs::write(big.join("b.mp4"), vec![0u8; 3000]).unwrap();
+        fs::write(small.join("c.log"), vec![0u8; 4000]).unwrap();
+
+        let options = UsageOptions { top: 2, ..Default::default() };
+        let report = analyze(&[dir.path()], &HashSet::new(), &options);
+
+        let files: Vec<&str> = report.files.iter().map(|f| f.path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(files, vec!["a.mp4", "c.log"]);
+        assert_eq!(report.dirs[0].path, dir.path());
+        assert_eq!(report.dirs[0].apparent, 12000);
+        assert_eq!(report.dirs[1].path, big);
+        assert_eq!(report.dirs[1].files, 2);
+
+        let options = UsageOptions { extensions: ["log".to_string()].into(), ..Default::default() };
+        let report = analyze(&[dir.path()], &HashSet::new(), &options);
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.dirs[0].apparent, 4000);
+        assert_eq!(matching_files(&small, &HashSet::new(), &options), vec![small.join("c.log")]);
+    }
+}